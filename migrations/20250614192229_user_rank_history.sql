@@ -0,0 +1,61 @@
+-- Statistiques agrégées par utilisateur et par mode (pp pondéré, précision, rangs)
+-- Le pp total suit la pondération officielle : chaque meilleur score par beatmap
+-- est multiplié par 0.95^n selon sa position dans le top de l'utilisateur.
+create or replace view user_mode_stats as
+with best_per_beatmap as (
+    select distinct on (s.user_id, b.mode, s.beatmap_id)
+        s.user_id,
+        b.mode,
+        s.accuracy,
+        sr.rating_value as pp
+    from score s
+    join beatmap b on b.id = s.beatmap_id
+    join score_rating sr on sr.score_id = s.id
+    join rating_type rt on rt.id = sr.rating_type_id and rt.name = 'pp'
+    order by s.user_id, b.mode, s.beatmap_id, sr.rating_value desc
+),
+weighted as (
+    select
+        user_id,
+        mode,
+        accuracy,
+        pp,
+        power(0.95, row_number() over (partition by user_id, mode order by pp desc) - 1) as weight
+    from best_per_beatmap
+),
+totals as (
+    select
+        user_id,
+        mode,
+        sum(pp * weight) as pp,
+        sum(accuracy * weight) / sum(weight) as accuracy
+    from weighted
+    group by user_id, mode
+)
+select
+    t.user_id,
+    t.mode,
+    u.country,
+    t.pp::decimal(10,3) as pp,
+    t.accuracy::decimal(6,3) as accuracy,
+    (rank() over (partition by t.mode order by t.pp desc))::integer as global_rank,
+    (rank() over (partition by t.mode, u.country order by t.pp desc))::integer as country_rank
+from totals t
+join users u on u.id = t.user_id;
+
+-- Historique quotidien des rangs et du pp
+create table if not exists user_rank_history (
+    id serial primary key,
+    user_id integer not null references users(id) on delete cascade,
+    mode integer not null check (mode >= 0 and mode <= 3),
+    global_rank integer not null,
+    country_rank integer not null,
+    pp decimal(10,3) not null,
+    accuracy decimal(6,3) not null,
+    snapshot_date date not null,
+    created_at timestamp default now(),
+    unique (user_id, mode, snapshot_date)
+);
+
+create index if not exists idx_user_rank_history_user_mode on user_rank_history(user_id, mode, snapshot_date);
+create index if not exists idx_user_rank_history_snapshot_date on user_rank_history(snapshot_date);
@@ -0,0 +1,22 @@
+-- Skillsets Etterna (MSD) par beatmap mania et par mods de vitesse (DT/NC/HT)
+create table if not exists beatmap_skillset (
+    id serial primary key,
+    beatmap_id integer not null references beatmap(id) on delete cascade,
+    rate_mods integer not null default 0,
+    overall decimal(10,3) not null,
+    skillsets jsonb not null,
+    created_at timestamp default now(),
+    updated_at timestamp default now(),
+    unique (beatmap_id, rate_mods)
+);
+
+-- Skillsets agrégés par joueur
+create table if not exists user_skillset (
+    user_id integer primary key references users(id) on delete cascade,
+    overall decimal(10,3) not null,
+    skillsets jsonb not null,
+    updated_at timestamp default now()
+);
+
+create index if not exists idx_beatmap_skillset_beatmap_id on beatmap_skillset(beatmap_id);
+create index if not exists idx_user_skillset_overall on user_skillset(overall);
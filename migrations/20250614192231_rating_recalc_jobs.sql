@@ -0,0 +1,39 @@
+-- Version du calculateur ayant produit chaque rating
+alter table score_rating add column if not exists calculator_version integer not null default 1;
+
+-- Jobs de recalcul des ratings, traités en tâche de fond
+create table if not exists rating_recalc_job (
+    id serial primary key,
+    status varchar(20) not null default 'pending',
+    rating_type varchar(50),
+    mode integer,
+    beatmap_id integer references beatmap(id) on delete set null,
+    user_id integer references users(id) on delete set null,
+    only_outdated boolean not null default false,
+    cursor_score_id integer not null default 0,
+    total_scores integer not null default 0,
+    processed_scores integer not null default 0,
+    failed_scores integer not null default 0,
+    error text,
+    created_by integer references users(id) on delete set null,
+    created_at timestamp default now(),
+    started_at timestamp,
+    finished_at timestamp,
+    updated_at timestamp default now(),
+    constraint valid_status check (status in ('pending', 'running', 'completed', 'failed', 'cancelled'))
+);
+
+-- Écarts avant/après recalcul, pour repérer les plus gros changements d'un rework
+create table if not exists rating_recalc_delta (
+    id serial primary key,
+    job_id integer not null references rating_recalc_job(id) on delete cascade,
+    score_id integer not null references score(id) on delete cascade,
+    rating_type_id integer not null references rating_type(id) on delete cascade,
+    old_value decimal(10,3) not null,
+    new_value decimal(10,3) not null,
+    created_at timestamp default now()
+);
+
+create index if not exists idx_rating_recalc_job_status on rating_recalc_job(status);
+create index if not exists idx_rating_recalc_delta_job_id on rating_recalc_delta(job_id);
+create index if not exists idx_score_rating_version on score_rating(rating_type_id, calculator_version);
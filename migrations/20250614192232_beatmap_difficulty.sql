@@ -0,0 +1,22 @@
+-- Cache des attributs de difficulté par beatmap, mods influant sur la difficulté et version du calcul
+create table if not exists beatmap_difficulty (
+    id serial primary key,
+    beatmap_id integer not null references beatmap(id) on delete cascade,
+    mods integer not null default 0,
+    calculator_version integer not null,
+    stars decimal(10,4) not null,
+    aim decimal(10,4),
+    speed decimal(10,4),
+    flashlight decimal(10,4),
+    max_combo integer not null,
+    ar decimal(6,3) not null,
+    od decimal(6,3) not null,
+    hp decimal(6,3) not null,
+    cs decimal(6,3) not null,
+    attributes jsonb not null,
+    created_at timestamp default now(),
+    unique (beatmap_id, mods, calculator_version)
+);
+
+create index if not exists idx_beatmap_difficulty_beatmap_id on beatmap_difficulty(beatmap_id);
+create index if not exists idx_beatmap_difficulty_mods_stars on beatmap_difficulty(mods, calculator_version, stars);
@@ -0,0 +1,4 @@
+-- Rating qu'aurait obtenu le score en full combo (misses comptés comme des 300).
+-- max_rating contient le rating d'un SS avec les mêmes mods.
+alter table score_rating add column if not exists fc_rating decimal(10,3);
+alter table score_rating add constraint valid_fc_rating check (fc_rating is null or fc_rating >= 0);
@@ -0,0 +1,4 @@
+-- Détail du rating propre à chaque système (ex. composantes aim/speed/accuracy/flashlight du pp)
+alter table score_rating add column if not exists attributes jsonb;
+
+create index if not exists idx_score_rating_attributes on score_rating using gin (attributes);
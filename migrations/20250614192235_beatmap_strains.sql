@@ -0,0 +1,12 @@
+-- Cache des courbes de strain (sous-échantillonnées) par beatmap, mods et version du calcul
+create table if not exists beatmap_strains (
+    id serial primary key,
+    beatmap_id integer not null references beatmap(id) on delete cascade,
+    mods integer not null default 0,
+    calculator_version integer not null,
+    graph jsonb not null,
+    created_at timestamp default now(),
+    unique (beatmap_id, mods, calculator_version)
+);
+
+create index if not exists idx_beatmap_strains_beatmap_id on beatmap_strains(beatmap_id);
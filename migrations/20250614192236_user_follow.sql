@@ -0,0 +1,11 @@
+-- Liste d'amis : un utilisateur suit d'autres utilisateurs
+create table if not exists user_follow (
+    follower_id integer not null references users(id) on delete cascade,
+    followed_id integer not null references users(id) on delete cascade,
+    created_at timestamp default now(),
+    primary key (follower_id, followed_id),
+    constraint no_self_follow check (follower_id <> followed_id)
+);
+
+create index if not exists idx_user_follow_followed_id on user_follow(followed_id);
+create index if not exists idx_users_country on users(country);
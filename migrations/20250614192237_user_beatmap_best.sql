@@ -0,0 +1,56 @@
+-- Scores masqués : conservés mais exclus des classements
+alter table score add column if not exists hidden boolean not null default false;
+
+-- Meilleur score de chaque joueur par beatmap, combinaison de mods et critère de classement.
+-- mods = -1 regroupe toutes les combinaisons de mods. Les critères sont score, accuracy,
+-- combo, date et le nom de chaque rating_type actif. Maintenue par UserBeatmapBest::refresh
+-- dans la transaction qui modifie le score ou son rating.
+create table if not exists user_beatmap_best (
+    user_id integer not null references users(id) on delete cascade,
+    beatmap_id integer not null references beatmap(id) on delete cascade,
+    mode integer not null,
+    mods integer not null,
+    criteria varchar(20) not null,
+    score_id integer not null references score(id) on delete cascade,
+    value numeric not null,
+    score integer not null,
+    updated_at timestamp default now(),
+    primary key (beatmap_id, mods, criteria, user_id)
+);
+
+create index if not exists idx_user_beatmap_best_ranking
+    on user_beatmap_best(beatmap_id, mods, criteria, value desc, score desc, score_id) include (user_id);
+create index if not exists idx_user_beatmap_best_user
+    on user_beatmap_best(user_id, mode, criteria, mods);
+create index if not exists idx_user_beatmap_best_score_id on user_beatmap_best(score_id);
+
+-- Remplissage initial
+insert into user_beatmap_best (user_id, beatmap_id, mode, mods, criteria, score_id, value, score)
+select distinct on (c.user_id, c.beatmap_id, c.mods_bucket, c.criteria)
+    c.user_id, c.beatmap_id, c.mode, c.mods_bucket, c.criteria, c.id, c.value, c.score
+from (
+    select s.id, s.user_id, s.beatmap_id, b.mode, s.score, bucket.mods_bucket, crit.criteria,
+        case crit.criteria
+            when 'score' then s.score::numeric
+            when 'accuracy' then s.accuracy
+            when 'combo' then s.max_combo::numeric
+            when 'date' then extract(epoch from s.created_at)::numeric
+            else (
+                select sr.rating_value from score_rating sr
+                join rating_type rt on rt.id = sr.rating_type_id
+                where sr.score_id = s.id and rt.name = crit.criteria
+            )
+        end as value
+    from score s
+    join beatmap b on b.id = s.beatmap_id
+    cross join lateral (values (-1), (s.mods)) as bucket(mods_bucket)
+    cross join (
+        select unnest(array['score', 'accuracy', 'combo', 'date']::varchar[]) as criteria
+        union all
+        select name from rating_type where is_active
+    ) as crit
+    where not s.hidden
+) c
+where c.value is not null
+order by c.user_id, c.beatmap_id, c.mods_bucket, c.criteria, c.value desc, c.score desc, c.id
+on conflict do nothing;
@@ -0,0 +1,48 @@
+-- Détenteur actuel de la première place de chaque beatmap (classement au score, tous mods)
+create table if not exists beatmap_first_place (
+    beatmap_id integer primary key references beatmap(id) on delete cascade,
+    user_id integer not null references users(id) on delete cascade,
+    -- null si le score a été supprimé depuis : la place sera réattribuée au prochain recalcul
+    score_id integer references score(id) on delete set null,
+    since timestamp not null default now()
+);
+
+create index if not exists idx_beatmap_first_place_user_id on beatmap_first_place(user_id, since desc);
+
+-- Historique des changements de première place
+create table if not exists first_place_history (
+    id serial primary key,
+    beatmap_id integer not null references beatmap(id) on delete cascade,
+    user_id integer not null references users(id) on delete cascade,
+    score_id integer not null references score(id) on delete cascade,
+    previous_user_id integer references users(id) on delete set null,
+    previous_score_id integer references score(id) on delete set null,
+    -- true si la place a été prise par un meilleur score, false si l'ancien score a été supprimé ou masqué
+    sniped boolean not null default true,
+    created_at timestamp not null default now()
+);
+
+create index if not exists idx_first_place_history_beatmap_id on first_place_history(beatmap_id, created_at desc);
+create index if not exists idx_first_place_history_user_id on first_place_history(user_id, created_at desc);
+create index if not exists idx_first_place_history_previous_user_id on first_place_history(previous_user_id, created_at desc);
+create index if not exists idx_first_place_history_sniped on first_place_history(created_at desc) where sniped;
+
+-- Événements à destination des notifications et webhooks, écrits dans la transaction
+-- qui les produit ; un NOTIFY sur le canal "events" est envoyé à la validation.
+create table if not exists event (
+    id bigserial primary key,
+    event_type varchar(50) not null,
+    payload jsonb not null,
+    created_at timestamp not null default now()
+);
+
+create index if not exists idx_event_type on event(event_type, id);
+
+-- Remplissage initial à partir des meilleurs scores
+insert into beatmap_first_place (beatmap_id, user_id, score_id, since)
+select distinct on (best.beatmap_id) best.beatmap_id, best.user_id, best.score_id, coalesce(s.created_at, now())
+from user_beatmap_best best
+join score s on s.id = best.score_id
+where best.mods = -1 and best.criteria = 'score'
+order by best.beatmap_id, best.value desc, best.score desc, best.score_id
+on conflict do nothing;
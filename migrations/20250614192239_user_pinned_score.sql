@@ -0,0 +1,8 @@
+-- Scores épinglés par un utilisateur sur son profil
+create table if not exists user_pinned_score (
+    score_id integer primary key references score(id) on delete cascade,
+    user_id integer not null references users(id) on delete cascade,
+    pinned_at timestamp not null default now()
+);
+
+create index if not exists idx_user_pinned_score_user on user_pinned_score(user_id, pinned_at desc);
@@ -0,0 +1,12 @@
+-- Historique des changements de pseudo
+-- Un ancien pseudo reste associé à son utilisateur (et réservé) pendant une période de grâce
+create table if not exists username_history (
+    id serial primary key,
+    user_id integer not null references users(id) on delete cascade,
+    old_username varchar(32) not null,
+    new_username varchar(32) not null,
+    changed_at timestamp not null default now()
+);
+
+create index if not exists idx_username_history_user on username_history(user_id, changed_at desc);
+create index if not exists idx_username_history_old_username on username_history(old_username, changed_at desc);
@@ -0,0 +1,13 @@
+-- Jetons de vérification d'adresse email
+-- Seul le hash sha256 du jeton est stocké ; un jeton n'est utilisable qu'une fois et pour l'email auquel il a été envoyé
+create table if not exists email_verification_token (
+    id serial primary key,
+    user_id integer not null references users(id) on delete cascade,
+    token_hash varchar(64) not null unique,
+    email varchar(255) not null,
+    expires_at timestamp not null,
+    used_at timestamp,
+    created_at timestamp not null default now()
+);
+
+create index if not exists idx_email_verification_token_user on email_verification_token(user_id, created_at desc);
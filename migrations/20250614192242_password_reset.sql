@@ -0,0 +1,15 @@
+-- Réinitialisation de mot de passe
+-- Seul le hash sha256 du jeton est stocké ; un jeton n'est utilisable qu'une fois
+create table if not exists password_reset_token (
+    id serial primary key,
+    user_id integer not null references users(id) on delete cascade,
+    token_hash varchar(64) not null unique,
+    expires_at timestamp not null,
+    used_at timestamp,
+    created_at timestamp not null default now()
+);
+
+create index if not exists idx_password_reset_token_user on password_reset_token(user_id, created_at desc);
+
+-- Les JWT émis avant cette date sont refusés (réinitialisation du mot de passe)
+alter table users add column if not exists sessions_revoked_at timestamp;
@@ -0,0 +1,19 @@
+-- Sessions de connexion
+-- Chaque session porte un refresh token (hash sha256) renouvelé à chaque utilisation ;
+-- les JWT d'accès référencent la session et sont refusés une fois celle-ci révoquée
+create table if not exists user_session (
+    id serial primary key,
+    user_id integer not null references users(id) on delete cascade,
+    refresh_token_hash varchar(64) not null unique,
+    -- Refresh token précédent : sa réutilisation indique un vol et révoque la session
+    previous_token_hash varchar(64),
+    user_agent varchar(255),
+    ip_address varchar(45),
+    created_at timestamp not null default now(),
+    last_used_at timestamp not null default now(),
+    expires_at timestamp not null,
+    revoked_at timestamp
+);
+
+create index if not exists idx_user_session_user on user_session(user_id, last_used_at desc);
+create index if not exists idx_user_session_previous_token on user_session(previous_token_hash);
@@ -0,0 +1,7 @@
+-- Les comptes créés avant la vérification d'email sont considérés comme vérifiés
+-- Le premier jeton émis marque la mise en service de la vérification ; les comptes ayant
+-- déjà reçu un jeton (inscription ou changement d'email depuis) gardent leur état
+update users set is_verified = true
+where not is_verified
+and created_at < (select coalesce(min(created_at), now()) from email_verification_token)
+and not exists (select 1 from email_verification_token t where t.user_id = users.id);
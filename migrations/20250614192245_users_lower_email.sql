@@ -0,0 +1,2 @@
+-- Recherche des comptes par email sans tenir compte de la casse (mot de passe oublié)
+create index if not exists idx_users_lower_email on users(lower(email));
@@ -0,0 +1,17 @@
+-- Refresh tokens déjà renouvelés de chaque session
+-- La réutilisation de n'importe lequel d'entre eux indique un vol et révoque la session ;
+-- ils sont supprimés avec la session
+create table if not exists user_session_rotated_token (
+    token_hash varchar(64) primary key,
+    session_id integer not null references user_session(id) on delete cascade,
+    rotated_at timestamp not null default now()
+);
+
+create index if not exists idx_user_session_rotated_token_session on user_session_rotated_token(session_id);
+
+insert into user_session_rotated_token (token_hash, session_id)
+select previous_token_hash, id from user_session where previous_token_hash is not null
+on conflict do nothing;
+
+drop index if exists idx_user_session_previous_token;
+alter table user_session drop column if exists previous_token_hash;
@@ -0,0 +1,43 @@
+-- Les scores masqués ne comptent plus dans les statistiques (pp, précision, rangs)
+create or replace view user_mode_stats as
+with best_per_beatmap as (
+    select distinct on (s.user_id, b.mode, s.beatmap_id)
+        s.user_id,
+        b.mode,
+        s.accuracy,
+        sr.rating_value as pp
+    from score s
+    join beatmap b on b.id = s.beatmap_id
+    join score_rating sr on sr.score_id = s.id
+    join rating_type rt on rt.id = sr.rating_type_id and rt.name = 'pp'
+    where not s.hidden
+    order by s.user_id, b.mode, s.beatmap_id, sr.rating_value desc
+),
+weighted as (
+    select
+        user_id,
+        mode,
+        accuracy,
+        pp,
+        power(0.95, row_number() over (partition by user_id, mode order by pp desc) - 1) as weight
+    from best_per_beatmap
+),
+totals as (
+    select
+        user_id,
+        mode,
+        sum(pp * weight) as pp,
+        sum(accuracy * weight) / sum(weight) as accuracy
+    from weighted
+    group by user_id, mode
+)
+select
+    t.user_id,
+    t.mode,
+    u.country,
+    t.pp::decimal(10,3) as pp,
+    t.accuracy::decimal(6,3) as accuracy,
+    (rank() over (partition by t.mode order by t.pp desc))::integer as global_rank,
+    (rank() over (partition by t.mode, u.country order by t.pp desc))::integer as country_rank
+from totals t
+join users u on u.id = t.user_id;
@@ -1,18 +1,26 @@
 use axum::{
     body::Body,
     response::IntoResponse,
-    extract::{Request, Json, State},
+    extract::{ConnectInfo, Extension, Request, Json, State},
     http,
-    http::{Response, StatusCode},
+    http::{HeaderMap, Response, StatusCode},
     middleware::Next,
 };
 use bcrypt::{hash, verify, DEFAULT_COST};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::PgPool;
-use crate::models::user::user::User;
+use tracing::error;
+use crate::handlers::auth::send_email_verification;
+use crate::helpers::mailer::MailService;
+use crate::models::user::user::{CreateUser, User, UserCreation};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use crate::models::user::session::{CurrentSession, SessionClient, UserSession};
+use crate::config::AuthConfig;
+use validator::{Validate, ValidationErrors};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -20,6 +28,7 @@ pub struct Claims {
     pub iat: usize,
     pub sub: String, // user_id
     pub username: String,
+    pub sid: i32, // user_session.id
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,6 +40,8 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginResponse {
     pub token: String,
+    /// Refresh token de la session, à échanger contre un nouveau JWT via /api/auth/refresh
+    pub refresh_token: String,
     pub user: User,
     pub roles: Vec<String>,
 }
@@ -67,12 +78,46 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::Bcryp
     verify(password, hash)
 }
 
-pub fn encode_jwt(user: &User) -> Result<String, StatusCode> {
-    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
+/// Clés JWT chargées depuis `AuthConfig` : une clé de signature, toutes les clés de vérification,
+/// et les durées de vie des JWT d'accès et des sessions
+///
+/// Partagées entre les handlers et les middlewares via une `Extension`.
+#[derive(Clone)]
+pub struct JwtKeys {
+    kid: String,
+    encoding: EncodingKey,
+    decoding: HashMap<String, DecodingKey>,
+    expiry: Duration,
+    session_expiry_days: i32,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &AuthConfig) -> Self {
+        let mut decoding = HashMap::new();
+        decoding.insert(config.jwt_key_id.clone(), DecodingKey::from_secret(config.jwt_secret.as_bytes()));
+        for key in &config.previous_keys {
+            decoding.insert(key.kid.clone(), DecodingKey::from_secret(key.secret.as_bytes()));
+        }
+
+        Self {
+            kid: config.jwt_key_id.clone(),
+            encoding: EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+            decoding,
+            expiry: Duration::minutes(config.access_token_expiry_minutes),
+            session_expiry_days: config.refresh_token_expiry_days,
+        }
+    }
+
+    /// Durée d'inactivité après laquelle une session expire
+    pub fn session_expiry_days(&self) -> i32 {
+        self.session_expiry_days
+    }
+}
 
+/// Émet un JWT d'accès rattaché à une session
+pub fn encode_jwt(keys: &JwtKeys, user: &User, session_id: i32) -> Result<String, StatusCode> {
     let now = Utc::now();
-    let expire = Duration::hours(24);
-    let exp = (now + expire).timestamp() as usize;
+    let exp = (now + keys.expiry).timestamp() as usize;
     let iat = now.timestamp() as usize;
 
     let claims = Claims {
@@ -80,25 +125,25 @@ pub fn encode_jwt(user: &User) -> Result<String, StatusCode> {
         exp,
         sub: user.id.to_string(),
         username: user.username.clone(),
+        sid: session_id,
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some(keys.kid.clone());
+
+    encode(&header, &claims, &keys.encoding)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-pub fn decode_jwt(token: &str) -> Result<TokenData<Claims>, StatusCode> {
-    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
+/// Vérifie un JWT avec la clé désignée par son en-tête `kid`
+pub fn decode_jwt(keys: &JwtKeys, token: &str) -> Result<TokenData<Claims>, StatusCode> {
+    let header = decode_header(token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let key = header.kid
+        .and_then(|kid| keys.decoding.get(&kid))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    decode(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    decode(token, key, &Validation::new(Algorithm::HS256))
+        .map_err(|_| StatusCode::UNAUTHORIZED)
 }
 
 pub async fn authorize(
@@ -128,7 +173,12 @@ pub async fn authorize(
             status_code: StatusCode::UNAUTHORIZED,
         })?;
 
-    let token_data = match decode_jwt(token) {
+    let keys = req.extensions().get::<JwtKeys>().ok_or_else(|| AuthError {
+        message: "JWT keys not configured".to_string(),
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+    })?;
+
+    let token_data = match decode_jwt(keys, token) {
         Ok(data) => data,
         Err(_) => {
             return Err(AuthError {
@@ -138,23 +188,51 @@ pub async fn authorize(
         }
     };
 
-    // Récupérer l'utilisateur depuis la base de données
-    let user = match User::get_by_id(&pool, token_data.claims.sub.parse().unwrap()).await {
+    // Récupérer l'utilisateur de la session, si elle est encore active
+    let user_id = token_data.claims.sub.parse().map_err(|_| AuthError {
+        message: "Invalid token".to_string(),
+        status_code: StatusCode::UNAUTHORIZED,
+    })?;
+    let user = match UserSession::get_active_user(&pool, token_data.claims.sid, user_id).await {
         Ok(Some(user)) => user,
         _ => {
             return Err(AuthError {
-                message: "User not found".to_string(),
+                message: "Session revoked or user not found".to_string(),
                 status_code: StatusCode::UNAUTHORIZED,
             })
         }
     };
 
+    if !user.accepts_token_issued_at(token_data.claims.iat) {
+        return Err(AuthError {
+            message: "Token revoked".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        });
+    }
+
     req.extensions_mut().insert(user);
+    req.extensions_mut().insert(CurrentSession(token_data.claims.sid));
     Ok(next.run(req).await)
 }
 
+/// Appareil et adresse du client, enregistrés avec sa session
+pub fn session_client(headers: &HeaderMap, address: SocketAddr) -> SessionClient {
+    let user_agent = headers
+        .get(http::header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(255).collect());
+
+    SessionClient {
+        user_agent,
+        ip_address: Some(address.ip().to_string()),
+    }
+}
+
 pub async fn login(
     State(pool): State<PgPool>,
+    Extension(keys): Extension<JwtKeys>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(login_data): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, StatusCode> {
     // Récupérer l'utilisateur
@@ -170,43 +248,179 @@ pub async fn login(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    // Générer le JWT
-    let token = encode_jwt(&user).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Ouvrir une session et générer le JWT
+    let (session_id, refresh_token) = UserSession::create(&pool, user.id, &session_client(&headers, address), keys.session_expiry_days())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let token = encode_jwt(&keys, &user, session_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Extraire les rôles
     let roles = user.get_roles();
 
-    Ok(Json(LoginResponse { token, user, roles }))
+    Ok(Json(LoginResponse { token, refresh_token, user, roles }))
+}
+
+/// Erreurs de validation par champ : `{"errors": {"username": ["taken"]}}`
+pub struct FieldErrors {
+    pub status_code: StatusCode,
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl FieldErrors {
+    fn new(status_code: StatusCode) -> Self {
+        Self { status_code, errors: BTreeMap::new() }
+    }
+
+    fn add(&mut self, field: &str, code: &str) {
+        self.errors.entry(field.to_string()).or_default().push(code.to_string());
+    }
+}
+
+impl From<ValidationErrors> for FieldErrors {
+    fn from(validation: ValidationErrors) -> Self {
+        let mut errors = Self::new(StatusCode::BAD_REQUEST);
+        for (field, field_errors) in validation.field_errors() {
+            for error in field_errors {
+                errors.add(&field, &error.code);
+            }
+        }
+        errors
+    }
+}
+
+impl IntoResponse for FieldErrors {
+    fn into_response(self) -> Response<Body> {
+        (self.status_code, Json(json!({ "errors": self.errors }))).into_response()
+    }
 }
 
 pub async fn register(
     State(pool): State<PgPool>,
+    Extension(keys): Extension<JwtKeys>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Extension(mail): Extension<MailService>,
     Json(register_data): Json<RegisterRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+) -> Result<Json<LoginResponse>, Response<Body>> {
+    let mut new_user = CreateUser {
+        username: register_data.username,
+        email: register_data.email,
+        password: register_data.password,
+        country: register_data.country.to_uppercase(),
+    };
+
+    // Valider les champs avant de hacher le mot de passe
+    let mut errors = match new_user.validate() {
+        Ok(()) => FieldErrors::new(StatusCode::BAD_REQUEST),
+        Err(validation) => FieldErrors::from(validation),
+    };
+    if !new_user.username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        errors.add("username", "invalid_characters");
+    }
+    if !errors.errors.is_empty() {
+        return Err(errors.into_response());
+    }
+
     // Hash du mot de passe
-    let password_hash = hash_password(&register_data.password)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    new_user.password = hash_password(&new_user.password)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
 
     // Créer l'utilisateur
-    let user = User::create(
-        &pool,
-        crate::models::user::user::CreateUser {
-            username: register_data.username,
-            email: register_data.email,
-            password: password_hash,
-            country: register_data.country,
-        },
-    )
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Générer le token
-    let token = encode_jwt(&user)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user = match User::create(&pool, new_user).await {
+        Ok(UserCreation::Created(user)) => *user,
+        Ok(UserCreation::Taken { username, email }) => {
+            let mut errors = FieldErrors::new(StatusCode::CONFLICT);
+            if username {
+                errors.add("username", "taken");
+            }
+            if email {
+                errors.add("email", "taken");
+            }
+            return Err(errors.into_response());
+        }
+        Err(e) => {
+            error!("Failed to create user: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    // Envoyer le lien de vérification ; un échec n'empêche pas l'inscription, le lien peut être renvoyé
+    if let Err(e) = send_email_verification(&pool, &mail, &user).await {
+        error!("Failed to issue verification token for user {}: {}", user.id, e);
+    }
+
+    // Ouvrir une session et générer le token
+    let (session_id, refresh_token) = UserSession::create(&pool, user.id, &session_client(&headers, address), keys.session_expiry_days())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    let token = encode_jwt(&keys, &user, session_id)
+        .map_err(|status| status.into_response())?;
 
     // Extraire les rôles
     let roles = user.get_roles();
 
-    Ok(Json(LoginResponse { token, user, roles }))
-} 
+    Ok(Json(LoginResponse { token, refresh_token, user, roles }))
+}
+
+
 
+/// Génère un jeton opaque aléatoire (256 bits, hexadécimal) pour les liens envoyés par email
+pub fn generate_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// Hash sha256 d'un jeton, seule forme stockée en base
+pub fn hash_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JwtKeyConfig;
+
+    fn keys() -> JwtKeys {
+        JwtKeys::from_config(&AuthConfig {
+            jwt_secret: "current-secret".to_string(),
+            access_token_expiry_minutes: 15,
+            refresh_token_expiry_days: 30,
+            jwt_key_id: "current".to_string(),
+            previous_keys: vec![JwtKeyConfig { kid: "previous".to_string(), secret: "previous-secret".to_string() }],
+        })
+    }
+
+    fn token(kid: Option<&str>, secret: &str) -> String {
+        let now = Utc::now().timestamp() as usize;
+        let claims = Claims { exp: now + 60, iat: now, sub: "1".to_string(), username: "test".to_string(), sid: 1 };
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = kid.map(str::to_string);
+
+        encode(&header, &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn decode_jwt_accepts_current_and_previous_keys() {
+        let keys = keys();
+        assert_eq!(decode_jwt(&keys, &token(Some("current"), "current-secret")).unwrap().claims.sid, 1);
+        assert_eq!(decode_jwt(&keys, &token(Some("previous"), "previous-secret")).unwrap().claims.sid, 1);
+    }
+
+    #[test]
+    fn decode_jwt_rejects_unknown_kid() {
+        let result = decode_jwt(&keys(), &token(Some("unknown"), "current-secret"));
+        assert_eq!(result.err(), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn decode_jwt_rejects_missing_kid() {
+        let result = decode_jwt(&keys(), &token(None, "current-secret"));
+        assert_eq!(result.err(), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn decode_jwt_rejects_secret_of_another_kid() {
+        let result = decode_jwt(&keys(), &token(Some("current"), "previous-secret"));
+        assert_eq!(result.err(), Some(StatusCode::UNAUTHORIZED));
+    }
+}
@@ -2,7 +2,7 @@ use crate::fixtures::common::FixtureManager;
 use sqlx::{Pool, Postgres};
 use fake::{Fake, Faker};
 use tracing::info;
-use crate::models::user::user::CreateUser;
+use crate::models::user::user::{CreateUser, User};
 
 pub fn create_user_faker(number: u32) -> Vec<CreateUser> {
     (0..number)
@@ -12,9 +12,10 @@ pub fn create_user_faker(number: u32) -> Vec<CreateUser> {
 
 pub async fn create_user_fixtures(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
     info!("Creating user...");
-    let fixture_manager = FixtureManager::new(pool.clone());
-    
-    fixture_manager.submit_fixtures(create_user_faker(100), "users").await?;
+    // Le mot de passe factice est stocké tel quel : ces comptes ne servent pas à se connecter
+    for user in create_user_faker(100) {
+        User::get_or_create(pool, user).await?;
+    }
     Ok(())
 }
 
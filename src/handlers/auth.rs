@@ -1,11 +1,318 @@
 use axum::{
     response::Json,
-    extract::Extension,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::{HeaderMap, StatusCode},
 };
+use std::net::SocketAddr;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::ToSchema;
+use validator::Validate;
+use crate::auth::{encode_jwt, hash_password, session_client, JwtKeys};
+use crate::helpers::mailer::MailService;
+use crate::models::user::email_verification::EmailVerificationToken;
+use crate::models::user::password_reset::PasswordResetToken;
+use crate::models::user::session::{CurrentSession, UserSession};
 use crate::models::user::user::User;
 
 pub async fn me(
     Extension(user): Extension<User>
 ) -> Json<User> {
     Json(user)
-} 
\ No newline at end of file
+}
+
+/// Crée un jeton de vérification pour l'email actuel de l'utilisateur et lui envoie le lien
+///
+/// Retourne `false` sans rien envoyer si un lien a été envoyé à cet email il y a moins d'une minute.
+/// L'envoi se fait en arrière-plan pour ne pas bloquer la requête sur le serveur SMTP ; ses
+/// échecs sont journalisés, le lien pouvant être renvoyé.
+pub async fn send_email_verification(pool: &PgPool, mail: &MailService, user: &User) -> Result<bool, sqlx::Error> {
+    let Some(token) = EmailVerificationToken::issue(pool, user).await? else {
+        return Ok(false);
+    };
+
+    let mail = mail.clone();
+    let (user_id, email, username) = (user.id, user.email.clone(), user.username.clone());
+    tokio::spawn(async move {
+        if let Err(e) = mail.send_email_verification(&email, &username, &token).await {
+            error!("Failed to send verification email to user {}: {}", user_id, e);
+        }
+    });
+
+    Ok(true)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyEmailRequest {
+    /// Token received by email
+    pub token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify",
+    tag = "Auth",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 204, description = "Email verified"),
+        (status = 400, description = "Unknown, expired or already used token")
+    ),
+    summary = "Verify an email address",
+    description = "Consume the single-use token sent by email and mark the account as verified. A token only verifies the address it was sent to"
+)]
+pub async fn verify_email(
+    State(pool): State<PgPool>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<StatusCode, StatusCode> {
+    match EmailVerificationToken::verify(&pool, &request.token).await {
+        Ok(Some(_)) => Ok(StatusCode::NO_CONTENT),
+        Ok(None) => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            error!("Failed to verify email token: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify/resend",
+    tag = "Auth",
+    responses(
+        (status = 204, description = "Verification email sent"),
+        (status = 401, description = "Not authenticated"),
+        (status = 409, description = "Email already verified"),
+        (status = 429, description = "A verification email was sent less than a minute ago")
+    ),
+    summary = "Resend the verification email",
+    description = "Send a new verification link to the email of the authenticated user. Previous links stop working"
+)]
+pub async fn resend_email_verification(
+    State(pool): State<PgPool>,
+    Extension(mail): Extension<MailService>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, StatusCode> {
+    if user.is_verified {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    match send_email_verification(&pool, &mail, &user).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::TOO_MANY_REQUESTS),
+        Err(e) => {
+            error!("Failed to issue verification token for user {}: {}", user.id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Envoie un lien de réinitialisation au compte associé à l'email, s'il existe
+///
+/// Ne fait rien si un lien a été envoyé à ce compte il y a moins d'une minute.
+async fn send_password_reset(pool: &PgPool, mail: &MailService, email: &str) -> Result<(), String> {
+    let Some(user) = User::get_by_email(pool, email).await.map_err(|e| e.to_string())? else {
+        return Ok(());
+    };
+
+    let Some(token) = PasswordResetToken::issue(pool, user.id).await.map_err(|e| e.to_string())? else {
+        return Ok(());
+    };
+    mail.send_password_reset(&user.email, &user.username, &token).await
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ForgotPasswordRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/forgot",
+    tag = "Auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 204, description = "Request accepted"),
+        (status = 400, description = "Invalid email")
+    ),
+    summary = "Request a password reset",
+    description = "Send a single-use password reset link, valid for 1 hour, to the account using this email. The response is the same whether or not an account exists"
+)]
+pub async fn forgot_password(
+    State(pool): State<PgPool>,
+    Extension(mail): Extension<MailService>,
+    Json(request): Json<ForgotPasswordRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if request.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Traité en arrière-plan pour que le temps de réponse ne révèle pas si le compte existe
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset(&pool, &mail, &request.email).await {
+            error!("Failed to send password reset email: {}", e);
+        }
+    });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// Token received by email
+    pub token: String,
+    #[validate(length(min = 8, max = 128))]
+    pub password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/reset",
+    tag = "Auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Invalid password, or unknown, expired or already used token")
+    ),
+    summary = "Reset the password",
+    description = "Consume a password reset token and set a new password. Every token issued before the reset stops working"
+)]
+pub async fn reset_password(
+    State(pool): State<PgPool>,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if request.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let password_hash = hash_password(&request.password)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    match PasswordResetToken::reset(&pool, &request.token, &password_hash).await {
+        Ok(Some(_)) => Ok(StatusCode::NO_CONTENT),
+        Ok(None) => Err(StatusCode::BAD_REQUEST),
+        Err(e) => {
+            error!("Failed to reset password: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RefreshResponse {
+    /// New access token
+    pub token: String,
+    /// New refresh token; the one sent is no longer valid
+    pub refresh_token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    tag = "Auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New token pair", body = RefreshResponse),
+        (status = 401, description = "Unknown, expired or revoked refresh token")
+    ),
+    summary = "Refresh the access token",
+    description = "Exchange the refresh token of a session for a new access token and a new refresh token. Each refresh token works once; reusing an already exchanged one revokes the session"
+)]
+pub async fn refresh_token(
+    State(pool): State<PgPool>,
+    Extension(keys): Extension<JwtKeys>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<RefreshResponse>, StatusCode> {
+    let client = session_client(&headers, address);
+    let refreshed = UserSession::refresh(&pool, &request.refresh_token, &client, keys.session_expiry_days())
+        .await
+        .map_err(|e| {
+            error!("Failed to refresh session: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let (user, session_id, refresh_token) = refreshed.ok_or(StatusCode::UNAUTHORIZED)?;
+    let token = encode_jwt(&keys, &user, session_id)?;
+
+    Ok(Json(RefreshResponse { token, refresh_token }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tag = "Auth",
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Not authenticated")
+    ),
+    summary = "Log out",
+    description = "Revoke the session of the access token: its refresh token and every access token issued for it stop working"
+)]
+pub async fn logout(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Extension(CurrentSession(session_id)): Extension<CurrentSession>,
+) -> Result<StatusCode, StatusCode> {
+    UserSession::revoke(&pool, user.id, session_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/sessions",
+    tag = "Auth",
+    responses(
+        (status = 200, description = "Active sessions, most recently used first", body = Vec<UserSession>),
+        (status = 401, description = "Not authenticated")
+    ),
+    summary = "List my sessions",
+    description = "Active sessions of the authenticated user, with device, IP address and last refresh. The session of the request is flagged as current"
+)]
+pub async fn get_sessions(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Extension(CurrentSession(session_id)): Extension<CurrentSession>,
+) -> Result<Json<Vec<UserSession>>, StatusCode> {
+    match UserSession::get_by_user(&pool, user.id, session_id).await {
+        Ok(sessions) => Ok(Json(sessions)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/auth/sessions/{id}",
+    tag = "Auth",
+    params(
+        ("id" = i32, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No active session with this ID for the user")
+    ),
+    summary = "Revoke one of my sessions",
+    description = "Log out a session of the authenticated user, for example a lost device"
+)]
+pub async fn revoke_session(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    match UserSession::revoke(&pool, user.id, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
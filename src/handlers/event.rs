@@ -0,0 +1,52 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::models::event::Event;
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
+pub struct EventParams {
+    /// Only return events with an id greater than this one (default: 0)
+    pub after_id: Option<i64>,
+    /// Only return events of this type (e.g. "first_place_taken")
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    /// Number of events returned (default: 100, max: 500)
+    #[validate(range(min = 1, max = 500))]
+    pub limit: Option<i64>,
+}
+
+/// Handler pour consommer les événements (notifications, webhooks)
+///
+/// Les consommateurs conservent le dernier id traité et le repassent dans `after_id`.
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "Event",
+    params(EventParams),
+    responses(
+        (status = 200, description = "Events found", body = Vec<Event>),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Admin role required")
+    ),
+    summary = "List events",
+    description = "List the events emitted after a given id, oldest first"
+)]
+pub async fn get_events(
+    State(pool): State<PgPool>,
+    Query(params): Query<EventParams>,
+) -> Result<Json<Vec<Event>>, StatusCode> {
+    params.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    match Event::get_after(&pool, params.after_id.unwrap_or(0), params.event_type.as_deref(), params.limit.unwrap_or(100)).await {
+        Ok(events) => Ok(Json(events)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
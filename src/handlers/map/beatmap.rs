@@ -5,27 +5,73 @@ use sqlx::PgPool;
 use serde::Deserialize;
 use bigdecimal::BigDecimal;
 use crate::models::map::beatmap::RandomBeatmapQuerySchema;
-use tracing::info;
+use crate::models::score::skillset::{BeatmapSkillset, SkillsetSchema};
+use crate::models::map::beatmap_difficulty::{BeatmapDifficulty, BeatmapDifficultySchema};
+use crate::models::map::beatmap_strains::BeatmapStrains;
+use crate::helpers::difficulty::{difficulty_mods, validate_mods, StrainGraph};
+use crate::helpers::pp::{simulate_pp, BeatmapFile, PpSimulation, PpSimulationInput};
+use validator::Validate;
+use utoipa::IntoParams;
+use tracing::{error, info, warn};
+
+/// Schéma d'une beatmap sous des mods, avec les étoiles du cache de difficulté
+///
+/// Utilisé par des listes publiques : rien n'est calculé ni téléchargé ici. Si les étoiles
+/// ne sont pas en cache, seuls les attributs et le BPM sont ajustés. `mods` doit avoir été
+/// validé par [`request_mods`].
+async fn beatmap_schema_with_mods(pool: &PgPool, beatmap: &Beatmap, mods: i32) -> BeatmapSchema {
+    if difficulty_mods(mods) == 0 {
+        return beatmap.to_schema();
+    }
+
+    let stars = match BeatmapDifficulty::get(pool, beatmap.id, mods).await {
+        Ok(difficulty) => difficulty.map(|difficulty| difficulty.to_schema().stars),
+        Err(e) => {
+            warn!("Étoiles indisponibles pour la beatmap {} avec les mods {}: {}", beatmap.id, mods, e);
+            None
+        }
+    };
+
+    beatmap.to_schema_with_mods(mods, stars)
+}
+
+/// Mods de la requête validés pour la beatmap, 400 pour une combinaison impossible
+fn request_mods(beatmap: &Beatmap, mods: Option<i32>) -> Result<i32, StatusCode> {
+    validate_mods(mods.unwrap_or(0), beatmap.mode).map_err(|_| StatusCode::BAD_REQUEST)
+}
 
 #[utoipa::path(
     get,
     path = "/api/beatmapset/{beatmapset_id}/beatmap",
     tag = "Beatmap",
+    params(
+        ("beatmapset_id" = i32, Path, description = "Beatmapset ID"),
+        AttributesParams
+    ),
     responses(
         (status = 200, description = "Beatmaps found", body = Vec<BeatmapSchema>),
+        (status = 400, description = "Conflicting mods for one of the beatmaps"),
         (status = 404, description = "Beatmapset not found")
     ),
     summary = "Get beatmaps by beatmapset id",
-    description = "Get all beatmaps by beatmapset id"
+    description = "Get all beatmaps by beatmapset id. With `mods`, AR/OD/CS/HP, BPM and lengths are the effective values under those mods, and the star rating too when it is already cached"
 )]
 pub async fn get_beatmap(
     State(pool): State<PgPool>,
-    Path(beatmapset_id): Path<i32>
-) -> Result<Json<Vec<Beatmap>>, StatusCode> {
-    match Beatmap::get_by_beatmapset(&pool, beatmapset_id).await {
-        Ok(beatmaps) => Ok(Json(beatmaps)),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    Path(beatmapset_id): Path<i32>,
+    Query(params): Query<AttributesParams>,
+) -> Result<Json<Vec<BeatmapSchema>>, StatusCode> {
+    let beatmaps = Beatmap::get_by_beatmapset(&pool, beatmapset_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut schemas = Vec::with_capacity(beatmaps.len());
+    for beatmap in &beatmaps {
+        let mods = request_mods(beatmap, params.mods)?;
+        schemas.push(beatmap_schema_with_mods(&pool, beatmap, mods).await);
     }
+
+    Ok(Json(schemas))
 }
 
 #[utoipa::path(
@@ -35,10 +81,12 @@ pub async fn get_beatmap(
     params(
         ("mode" = i32, Query, description = "Game mode"),
         ("status" = String, Query, description = "Beatmap status"),
-        ("limit_difficulty" = Option<f64>, Query, description = "Optional difficulty limit")
+        ("limit_difficulty" = Option<f64>, Query, description = "Optional difficulty limit"),
+        ("mods" = Option<i32>, Query, description = "Mods bitmask the difficulty limit applies to (uses cached mod-adjusted star ratings). The returned attributes are adjusted to these mods")
     ),
     responses(
         (status = 200, description = "Random beatmap found", body = BeatmapSchema),
+        (status = 400, description = "Conflicting mods"),
         (status = 404, description = "No beatmap found")
     ),
     summary = "Get a random beatmap",
@@ -47,21 +95,221 @@ pub async fn get_beatmap(
 pub async fn get_random(
     State(pool): State<PgPool>,
     Query(params): Query<RandomBeatmapQuerySchema>,
-) -> Result<Json<Beatmap>, StatusCode> {
+) -> Result<Json<BeatmapSchema>, StatusCode> {
     info!("Received params - mode: {}, status: {}, difficulty: {:?}", params.mode, params.status, params.limit_difficulty);
     
     // Ensure difficulty is positive if provided
     let limit_difficulty = params.limit_difficulty.and_then(|d: f64| if d <= 0.0 { None } else { Some(d) });
     
     info!("Processed limit_difficulty: {:?}", limit_difficulty);
+
+    let mods = params.mods
+        .map(|mods| validate_mods(mods, params.mode).map_err(|_| StatusCode::BAD_REQUEST))
+        .transpose()?;
     
-    match Beatmap::get_random_beatmap(&pool, params.mode, &params.status, limit_difficulty).await {
+    match Beatmap::get_random_beatmap(&pool, params.mode, &params.status, limit_difficulty, mods).await {
         Ok(Some(beatmap)) => {
             info!("Found beatmap with difficulty: {}", beatmap.difficulty_rating);
-            Ok(Json(beatmap))
+            Ok(Json(beatmap_schema_with_mods(&pool, &beatmap, mods.unwrap_or(0)).await))
         },
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
 
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SkillsetParams {
+    /// Mods bitmask, only rate mods (DT, NC, HT) are taken into account
+    pub mods: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/beatmap/{id}/skillsets",
+    tag = "Beatmap",
+    params(
+        ("id" = i32, Path, description = "Beatmap ID"),
+        SkillsetParams
+    ),
+    responses(
+        (status = 200, description = "Skillsets computed", body = SkillsetSchema),
+        (status = 400, description = "Beatmap is not a mania beatmap, or conflicting mods"),
+        (status = 404, description = "Beatmap not found")
+    ),
+    summary = "Get beatmap Etterna skillsets",
+    description = "Get the Etterna-style MSD skillsets of a mania beatmap, computed on first request and cached"
+)]
+pub async fn get_beatmap_skillsets(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<SkillsetParams>,
+) -> Result<Json<SkillsetSchema>, StatusCode> {
+    let beatmap = Beatmap::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if beatmap.mode != 3 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mods = request_mods(&beatmap, params.mods)?;
+    match BeatmapSkillset::get_or_calculate(&pool, &BeatmapFile::new(&beatmap), mods).await {
+        Ok(skillset) => Ok(Json(skillset.to_schema())),
+        Err(e) => {
+            error!("Échec du calcul des skillsets de la beatmap {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AttributesParams {
+    /// Mods bitmask, only difficulty-affecting mods are taken into account
+    pub mods: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/beatmap/{id}/attributes",
+    tag = "Beatmap",
+    params(
+        ("id" = i32, Path, description = "Beatmap ID"),
+        AttributesParams
+    ),
+    responses(
+        (status = 200, description = "Difficulty attributes", body = BeatmapDifficultySchema),
+        (status = 400, description = "Conflicting mods (EZ+HR, DT+HT, several key mods, key mods outside mania)"),
+        (status = 404, description = "Beatmap not found")
+    ),
+    summary = "Get beatmap difficulty attributes",
+    description = "Get the difficulty attributes (stars, aim/speed/flashlight, max combo, AR/OD/HP/CS after mods) of a beatmap for a mod combination, computed on first request and cached"
+)]
+pub async fn get_beatmap_attributes(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<AttributesParams>,
+) -> Result<Json<BeatmapDifficultySchema>, StatusCode> {
+    let beatmap = Beatmap::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mods = request_mods(&beatmap, params.mods)?;
+    match BeatmapDifficulty::get_or_calculate(&pool, &BeatmapFile::new(&beatmap), mods).await {
+        Ok(difficulty) => Ok(Json(difficulty.to_schema())),
+        Err(e) => {
+            error!("Échec du calcul des attributs de la beatmap {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/beatmap/{id}/strains",
+    tag = "Beatmap",
+    params(
+        ("id" = i32, Path, description = "Beatmap ID"),
+        AttributesParams
+    ),
+    responses(
+        (status = 200, description = "Strain graph", body = StrainGraph),
+        (status = 400, description = "Conflicting mods (EZ+HR, DT+HT, several key mods, key mods outside mania)"),
+        (status = 404, description = "Beatmap not found")
+    ),
+    summary = "Get beatmap strain graph",
+    description = "Get the strain values over time of each skill (aim, speed... depending on the mode) for a mod combination, downsampled to at most 200 points. `point_len` is the duration covered by each point in beatmap time, so point `i` starts at `i * point_len` ms"
+)]
+pub async fn get_beatmap_strains(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<AttributesParams>,
+) -> Result<Json<StrainGraph>, StatusCode> {
+    let beatmap = Beatmap::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mods = request_mods(&beatmap, params.mods)?;
+    match BeatmapStrains::get_or_calculate(&pool, &BeatmapFile::new(&beatmap), mods).await {
+        Ok(strains) => Ok(Json(strains.graph.0)),
+        Err(e) => {
+            error!("Échec du calcul des strains de la beatmap {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams, Validate)]
+pub struct PpSimulationParams {
+    /// Mods bitmask
+    pub mods: Option<i32>,
+    /// Accuracy in percent, used when hit counts are not given
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub accuracy: Option<f64>,
+    pub n300: Option<u32>,
+    pub n100: Option<u32>,
+    pub n50: Option<u32>,
+    pub n_katu: Option<u32>,
+    pub n_geki: Option<u32>,
+    /// Number of misses (default: 0)
+    pub misses: Option<u32>,
+    /// Max combo reached (default: beatmap max combo minus misses)
+    pub combo: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/beatmap/{id}/pp",
+    tag = "Beatmap",
+    params(
+        ("id" = i32, Path, description = "Beatmap ID"),
+        PpSimulationParams
+    ),
+    responses(
+        (status = 200, description = "pp computed", body = PpSimulation),
+        (status = 400, description = "Invalid parameters or conflicting mods"),
+        (status = 404, description = "Beatmap not found")
+    ),
+    summary = "Simulate pp on a beatmap",
+    description = "Compute the pp, star rating and pp breakdown (aim/speed/accuracy/flashlight) of a hypothetical score, from mods and either an accuracy or hit counts"
+)]
+pub async fn simulate_beatmap_pp(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<PpSimulationParams>,
+) -> Result<Json<PpSimulation>, StatusCode> {
+    if params.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let beatmap = Beatmap::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mods = request_mods(&beatmap, params.mods)?;
+    let difficulty = match BeatmapDifficulty::get_or_calculate(&pool, &BeatmapFile::new(&beatmap), mods).await {
+        Ok(difficulty) => difficulty,
+        Err(e) => {
+            error!("Échec du calcul des attributs de la beatmap {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let input = PpSimulationInput {
+        mods,
+        accuracy: params.accuracy,
+        n300: params.n300,
+        n100: params.n100,
+        n50: params.n50,
+        n_katu: params.n_katu,
+        n_geki: params.n_geki,
+        misses: params.misses.unwrap_or(0),
+        combo: params.combo,
+    };
+
+    Ok(Json(simulate_pp(difficulty.difficulty_attributes(), &input)))
+}
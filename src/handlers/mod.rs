@@ -13,4 +13,5 @@ pub mod map;
 pub mod score;
 pub mod auth;
 pub mod home;
-pub mod ranked;
\ No newline at end of file
+pub mod ranked;
+pub mod event;
@@ -1,176 +1,214 @@
 use axum::{
-    extract::{Query, State},
+    extract::{Extension, Path, Query, State},
     http::StatusCode,
     response::Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use sqlx::PgPool;
-use tracing::{error, info, warn};
+use tracing::{error, info};
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
-use bigdecimal::{BigDecimal, FromPrimitive};
 
-use crate::models::score::score_rating::{ScoreRating, RatingType, CreateScoreRating};
-use crate::models::score::score::Score;
+use crate::helpers::rating::RatingRegistry;
+use crate::models::common::PaginationParams;
 use crate::models::map::beatmap::Beatmap;
-use crate::helpers::pp::calculate_pp_for_score;
+use crate::models::score::rating_job::{CreateRatingRecalcJob, RatingDeltaSchema, RatingRecalcJob};
+use crate::models::user::user::User;
+
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
-pub struct PPCalculationParams {
-    /// Nombre maximum de scores à traiter en une fois (défaut: 100, max: 1000)
-    #[validate(range(min = 1, max = 1000))]
+pub struct DeltaParams {
+    /// Nombre d'écarts retournés (défaut: 50, max: 500)
+    #[validate(range(min = 1, max = 500))]
     pub limit: Option<i64>,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
-pub struct PPCalculationResponse {
-    pub success: bool,
-    pub message: String,
-    pub scores_processed: usize,
-    pub scores_failed: usize,
-    pub total_scores_without_pp: usize,
-}
-
-/// Handler pour recalculer TOUS les score_rating de type "pp"
-/// 
-/// Ce handler :
-/// 1. Récupère TOUS les scores 
-/// 2. Pour chaque score, récupère la beatmap associée
-/// 3. Calcule le PP en utilisant rosu-pp
-/// 4. Supprime l'ancien rating PP s'il existe et crée le nouveau
+/// Handler pour lancer un recalcul des score_rating en tâche de fond
+///
+/// Le job est traité par le worker de `RatingRecalcJob` : ce handler se contente
+/// de valider les filtres et d'enregistrer le job.
 #[utoipa::path(
     post,
-    path = "/api/scores/calculate-pp",
+    path = "/api/scores/rating-jobs",
     tag = "Score",
-    params(PPCalculationParams),
+    request_body = CreateRatingRecalcJob,
     responses(
-        (status = 200, description = "PP calculation completed", body = PPCalculationResponse),
-        (status = 400, description = "Invalid parameters"),
-        (status = 500, description = "Internal server error")
+        (status = 202, description = "Recalculation job queued", body = RatingRecalcJob),
+        (status = 400, description = "Invalid mode or unknown rating type"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Beatmap or user not found")
     ),
-    summary = "Recalculate ALL PP ratings",
-    description = "Recalculate and recreate ALL PP ratings for scores using rosu-pp"
+    summary = "Queue a rating recalculation job",
+    description = "Queue a background job recalculating every active rating (or only `rating_type`) for all scores matching the filters. With `only_outdated`, only ratings missing or produced by an older calculator version are recalculated"
 )]
-pub async fn calculate_missing_pp(
+pub async fn create_rating_job(
     State(pool): State<PgPool>,
-    Query(params): Query<PPCalculationParams>,
-) -> Result<Json<PPCalculationResponse>, StatusCode> {
-    info!("Starting PP calculation process");
-
-    // Validation des paramètres
-    if let Err(validation_errors) = params.validate() {
-        error!("Invalid parameters: {:?}", validation_errors);
+    Extension(user): Extension<User>,
+    Json(create_job): Json<CreateRatingRecalcJob>,
+) -> Result<(StatusCode, Json<RatingRecalcJob>), StatusCode> {
+    if create_job.mode.is_some_and(|mode| !(0..=3).contains(&mode)) {
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    let limit = params.limit.unwrap_or(100);
-    info!("Processing {} scores maximum", limit);
-
-    // Récupérer le type de rating "pp"
-    let pp_rating_type = match RatingType::get_by_name(&pool, "pp").await {
-        Ok(Some(rating_type)) => rating_type,
-        Ok(None) => {
-            error!("Rating type 'pp' not found in database");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-        Err(e) => {
-            error!("Failed to get rating type 'pp': {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    if let Some(rating_type) = &create_job.rating_type {
+        let registry = RatingRegistry::load(&pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if registry.only(rating_type).is_empty() {
+            return Err(StatusCode::BAD_REQUEST);
         }
-    };
+    }
 
-    // Récupérer TOUS les scores (pas seulement ceux sans PP)
-    let all_scores = match Score::get_all(&pool, Some(limit), Some(0)).await {
-        Ok(scores) => scores,
-        Err(e) => {
-            error!("Failed to get scores: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    if let Some(beatmap_id) = create_job.beatmap_id {
+        Beatmap::get_by_id(&pool, beatmap_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+    }
+
+    if let Some(user_id) = create_job.user_id {
+        User::get_by_id(&pool, user_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+    }
+
+    match RatingRecalcJob::create(&pool, create_job, user.id).await {
+        Ok(job) => {
+            info!("Rating job {} queued by user {}", job.id, user.id);
+            Ok((StatusCode::ACCEPTED, Json(job)))
         }
-    };
-
-    let total_scores = all_scores.len();
-    info!("Found {} scores to recalculate PP", total_scores);
-
-    let mut processed_count = 0;
-    let mut failed_count = 0;
-
-    for score in all_scores {
-        // Récupérer la beatmap associée
-        let beatmap = match Beatmap::get_by_id(&pool, score.beatmap_id).await {
-            Ok(Some(beatmap)) => beatmap,
-            Ok(None) => {
-                warn!("Beatmap {} not found for score {}", score.beatmap_id, score.id);
-                failed_count += 1;
-                continue;
-            }
-            Err(e) => {
-                error!("Failed to get beatmap {}: {}", score.beatmap_id, e);
-                failed_count += 1;
-                continue;
-            }
-        };
-
-        // Calculer le PP avec rosu-pp
-        let calculated_pp = match calculate_pp_for_score(&score, &beatmap).await {
-            Ok(pp_value) => pp_value,
-            Err(e) => {
-                error!("Failed to calculate PP for score {}: {}", score.id, e);
-                failed_count += 1;
-                continue;
-            }
-        };
-
-        // Créer le score_rating
-        let pp_decimal = match BigDecimal::from_f64(calculated_pp) {
-            Some(decimal) => decimal,
-            None => {
-                error!("Failed to convert PP value {} to BigDecimal", calculated_pp);
-                failed_count += 1;
-                continue;
-            }
-        };
-
-        let create_rating = CreateScoreRating {
-            score_id: score.id,
-            rating_type_id: pp_rating_type.id,
-            rating_value: pp_decimal,
-            max_rating: None, // TODO: Calculer max_rating si nécessaire
-        };
-
-        // Supprimer l'ancien rating PP s'il existe
-        if let Ok(existing_rating) = ScoreRating::get_by_score_and_type(&pool, score.id, &pp_rating_type.name).await {
-            if let Some(_) = existing_rating {
-                match sqlx::query!("DELETE FROM score_rating WHERE score_id = $1 AND rating_type_id = $2", score.id, pp_rating_type.id)
-                    .execute(&pool)
-                    .await 
-                {
-                    Ok(_) => info!("Deleted existing PP rating for score {}", score.id),
-                    Err(e) => warn!("Failed to delete existing PP rating for score {}: {}", score.id, e),
-                }
-            }
+        Err(e) => {
+            error!("Failed to create rating job: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
+    }
+}
 
-        match ScoreRating::create(&pool, create_rating).await {
-            Ok(_) => {
-                processed_count += 1;
-                info!("Created PP rating {} for score {}", calculated_pp, score.id);
-            }
-            Err(e) => {
-                error!("Failed to create score_rating for score {}: {}", score.id, e);
-                failed_count += 1;
-            }
-        }
+#[utoipa::path(
+    get,
+    path = "/api/scores/rating-jobs",
+    tag = "Score",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number"),
+        ("per_page" = Option<i64>, Query, description = "Jobs per page (max 50)")
+    ),
+    responses(
+        (status = 200, description = "Jobs found", body = Vec<RatingRecalcJob>),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Admin role required")
+    ),
+    summary = "List rating recalculation jobs",
+    description = "List rating recalculation jobs, most recent first"
+)]
+pub async fn get_rating_jobs(
+    State(pool): State<PgPool>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Vec<RatingRecalcJob>>, StatusCode> {
+    let per_page = params.get_per_page();
+    let offset = (params.get_page() - 1) * per_page;
+
+    match RatingRecalcJob::get_all(&pool, per_page, offset).await {
+        Ok(jobs) => Ok(Json(jobs)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
     }
+}
 
-    let response = PPCalculationResponse {
-        success: failed_count == 0,
-        message: format!("Processing completed: {} scores processed, {} failed", processed_count, failed_count),
-        scores_processed: processed_count,
-        scores_failed: failed_count,
-        total_scores_without_pp: total_scores,
-    };
+#[utoipa::path(
+    get,
+    path = "/api/scores/rating-jobs/{id}",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "Job status", body = RatingRecalcJob),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Job not found")
+    ),
+    summary = "Get a rating recalculation job",
+    description = "Get the status and progress of a rating recalculation job"
+)]
+pub async fn get_rating_job(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<RatingRecalcJob>, StatusCode> {
+    match RatingRecalcJob::get_by_id(&pool, id).await {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
 
-    info!("PP calculation process completed: {} processed, {} failed", processed_count, failed_count);
-    Ok(Json(response))
+#[utoipa::path(
+    post,
+    path = "/api/scores/rating-jobs/{id}/cancel",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "Job cancelled", body = RatingRecalcJob),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Job not found"),
+        (status = 409, description = "Job already finished")
+    ),
+    summary = "Cancel a rating recalculation job",
+    description = "Cancel a pending or running job. Ratings already recalculated are kept"
+)]
+pub async fn cancel_rating_job(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<RatingRecalcJob>, StatusCode> {
+    RatingRecalcJob::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    match RatingRecalcJob::cancel(&pool, id).await {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/scores/rating-jobs/{id}/deltas",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Job ID"),
+        DeltaParams
+    ),
+    responses(
+        (status = 200, description = "Biggest rating changes", body = Vec<RatingDeltaSchema>),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Job not found")
+    ),
+    summary = "Get rating changes of a job",
+    description = "Get the biggest rating changes (in absolute value) between before and after the recalculation"
+)]
+pub async fn get_rating_job_deltas(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<DeltaParams>,
+) -> Result<Json<Vec<RatingDeltaSchema>>, StatusCode> {
+    if params.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    RatingRecalcJob::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let deltas = RatingRecalcJob::get_deltas(&pool, id, params.limit.unwrap_or(50))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    Ok(Json(deltas.iter().map(|d| d.to_schema()).collect()))
+}
@@ -1,8 +1,15 @@
-use crate::models::score::score::{Score, Leaderboard, LeaderboardSchema};
+use crate::models::score::score::{Score, LeaderboardFilter, LeaderboardPage, LeaderboardPageSchema, LeaderboardSort, LeaderboardStanding, LeaderboardStandingSchema, ScoreDetailSchema, ScoreSchema};
+use crate::models::user::user::User;
+use crate::models::score::score_rating::ScoreRating;
+use crate::models::score::first_place::FirstPlaceChange;
+use crate::models::score::user_score::{PinResult, UserScore, MAX_PINNED_SCORES};
+use crate::models::common::PaginationParams;
+use crate::models::map::beatmap::Beatmap;
+use crate::helpers::pp::calculate_if_fc_pp;
 use axum::{response::Json, http::StatusCode};
-use axum::extract::{State, Query, Path};
+use axum::extract::{Extension, State, Query, Path};
 use sqlx::PgPool;
-use tracing::info;
+use tracing::{error, info, warn};
 use serde::Deserialize;
 use utoipa::IntoParams;
 use utoipa::ToSchema;
@@ -15,36 +22,389 @@ pub struct LeaderboardParams {
     #[validate(range(min = 1, max = 50))]
     pub per_page: Option<i64>,
     pub mods: Option<i32>,
+    /// Ranking criteria: "score" (default), "pp", "accuracy", "combo", "date" or "quaver"
+    pub sort: Option<String>,
+    /// Players ranked: "global" (default), "country" (the requesting user's country) or "friends" (the requesting user and the players they follow)
+    pub scope: Option<String>,
 }
 
 
+#[derive(Deserialize, Debug, IntoParams, ToSchema)]
+pub struct LeaderboardStandingParams {
+    pub mods: Option<i32>,
+    /// Ranking criteria: "score" (default), "pp", "accuracy", "combo", "date" or "quaver"
+    pub sort: Option<String>,
+    /// Players ranked: "global" (default), "country" (the looked-up user's country) or "friends" (the looked-up user and the players they follow)
+    pub scope: Option<String>,
+}
+
+/// Construit les filtres d'un classement, la portée "country" / "friends" étant relative à `user`
+fn leaderboard_filter(mods: Option<i32>, sort: Option<&str>, scope: Option<&str>, user: &User) -> Result<LeaderboardFilter, StatusCode> {
+    let sort = match sort {
+        None => LeaderboardSort::Score,
+        Some(sort) => LeaderboardSort::parse(sort).ok_or(StatusCode::BAD_REQUEST)?,
+    };
+
+    let mut filter = LeaderboardFilter { mods, sort, country: None, friends_of: None };
+    match scope {
+        None | Some("global") => {}
+        Some("country") => filter.country = Some(user.country.clone()),
+        Some("friends") => filter.friends_of = Some(user.id),
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    }
+
+    Ok(filter)
+}
+
 #[utoipa::path(
     get,
     path = "/api/leaderboard/{beatmap_id}",
     tag = "Score",
     params(LeaderboardParams),
     responses(
-        (status = 200, description = "Leaderboard retrieved successfully", body = Vec<LeaderboardSchema>),
+        (status = 200, description = "Leaderboard retrieved successfully", body = LeaderboardPageSchema),
+        (status = 400, description = "Invalid page, per_page (1 to 50), sort criteria or scope"),
         (status = 404, description = "Beatmap not found")
     ),
     summary = "Get leaderboard",
-    description = "Get leaderboard for a beatmap"
+    description = "Get leaderboard for a beatmap. The sort criteria selects each player's best score and orders the leaderboard; ties are broken by score then by submission order. With \"pp\" or \"quaver\", only scores with that rating are ranked. The requesting user's best score and position are returned in `user_best`, even outside the page"
 )]
 pub async fn get_leaderboard(
     State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
     Query(params): Query<LeaderboardParams>,
     Path(beatmap_id): Path<i32>,
-) -> Result<Json<Vec<Leaderboard>>, StatusCode> {
+) -> Result<Json<LeaderboardPage>, StatusCode> {
+    if params.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
 
-    let leaderboard = Score::get_leaderboard(&pool, beatmap_id, params.mods, page, per_page).await;
+    let filter = leaderboard_filter(params.mods, params.sort.as_deref(), params.scope.as_deref(), &user)?;
 
-    match leaderboard {
+    match Score::get_leaderboard(&pool, beatmap_id, &filter, Some(user.id), page, per_page).await {
         Ok(leaderboard) => Ok(Json(leaderboard)),
         Err(err) => {
             info!("Error getting leaderboard: {:?}", err);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
-}
\ No newline at end of file
+}
+
+async fn leaderboard_standing(
+    pool: &PgPool,
+    beatmap_id: i32,
+    params: &LeaderboardStandingParams,
+    user: &User,
+) -> Result<Json<LeaderboardStanding>, StatusCode> {
+    let filter = leaderboard_filter(params.mods, params.sort.as_deref(), params.scope.as_deref(), user)?;
+
+    match Score::get_leaderboard_standing(pool, beatmap_id, &filter, user.id).await {
+        Ok(Some(standing)) => Ok(Json(standing)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(err) => {
+            info!("Error getting leaderboard standing: {:?}", err);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/leaderboard/{beatmap_id}/me",
+    tag = "Score",
+    params(
+        ("beatmap_id" = i32, Path, description = "Beatmap ID"),
+        LeaderboardStandingParams
+    ),
+    responses(
+        (status = 200, description = "Standing of the requesting user", body = LeaderboardStandingSchema),
+        (status = 400, description = "Invalid sort criteria or scope"),
+        (status = 404, description = "No ranked score on this beatmap with these filters")
+    ),
+    summary = "Get my leaderboard standing",
+    description = "Get the requesting user's best score on a beatmap, its position, the number of ranked players and the percentage of players ranked at or below that position. Filters are the same as the leaderboard"
+)]
+pub async fn get_my_leaderboard_standing(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Query(params): Query<LeaderboardStandingParams>,
+    Path(beatmap_id): Path<i32>,
+) -> Result<Json<LeaderboardStanding>, StatusCode> {
+    leaderboard_standing(&pool, beatmap_id, &params, &user).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/leaderboard/{beatmap_id}/user/{user_id}",
+    tag = "Score",
+    params(
+        ("beatmap_id" = i32, Path, description = "Beatmap ID"),
+        ("user_id" = i32, Path, description = "User ID"),
+        LeaderboardStandingParams
+    ),
+    responses(
+        (status = 200, description = "Standing of the user", body = LeaderboardStandingSchema),
+        (status = 400, description = "Invalid sort criteria or scope"),
+        (status = 404, description = "User not found, or no ranked score on this beatmap with these filters")
+    ),
+    summary = "Get a user's leaderboard standing",
+    description = "Get a user's best score on a beatmap, its position, the number of ranked players and the percentage of players ranked at or below that position. The \"country\" and \"friends\" scopes are relative to the looked-up user"
+)]
+pub async fn get_user_leaderboard_standing(
+    State(pool): State<PgPool>,
+    Query(params): Query<LeaderboardStandingParams>,
+    Path((beatmap_id, user_id)): Path<(i32, i32)>,
+) -> Result<Json<LeaderboardStanding>, StatusCode> {
+    let user = User::get_by_id(&pool, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    leaderboard_standing(&pool, beatmap_id, &params, &user).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/score/{id}",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Score ID")
+    ),
+    responses(
+        (status = 200, description = "Score found", body = ScoreDetailSchema),
+        (status = 404, description = "Score not found")
+    ),
+    summary = "Get score details",
+    description = "Get a score with all its ratings and the pp it would have given as a full combo"
+)]
+pub async fn get_score(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<ScoreDetailSchema>, StatusCode> {
+    let score = Score::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let ratings = ScoreRating::get_named_by_score(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Le pp "if FC" est lu depuis le rating pp s'il est déjà calculé ; sinon il est optionnel :
+    // un échec de calcul ne doit pas empêcher l'affichage du score
+    let stored_fc_pp = ratings.iter()
+        .find(|r| r.rating_type == "pp")
+        .and_then(|r| r.fc_rating.as_ref())
+        .map(|fc| fc.to_string().parse::<f64>().unwrap_or(0.0));
+    let if_fc_pp = match stored_fc_pp {
+        Some(fc_pp) => Some(fc_pp),
+        None => match Beatmap::get_by_id(&pool, score.beatmap_id).await {
+            Ok(Some(beatmap)) => match calculate_if_fc_pp(&pool, &score, &beatmap).await {
+                Ok(pp) => Some(pp),
+                Err(e) => {
+                    warn!("Failed to calculate if-FC pp for score {}: {}", id, e);
+                    None
+                }
+            },
+            _ => None,
+        },
+    };
+
+    Ok(Json(ScoreDetailSchema {
+        score: score.to_schema(),
+        ratings: ratings.iter().map(|r| r.to_schema()).collect(),
+        if_fc_pp,
+    }))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateScore {
+    /// Hide the score from leaderboards and bests, or show it again
+    pub hidden: bool,
+}
+
+/// Vérifie que l'utilisateur peut modifier un score : le sien, ou n'importe lequel pour un admin
+async fn get_owned_score(pool: &PgPool, user: &User, id: i32) -> Result<Score, StatusCode> {
+    let score = Score::get_by_id(pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if score.user_id != user.id && !user.has_role("admin") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(score)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/score/{id}",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Score ID")
+    ),
+    request_body = UpdateScore,
+    responses(
+        (status = 200, description = "Score updated", body = ScoreSchema),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not the owner of the score"),
+        (status = 404, description = "Score not found")
+    ),
+    summary = "Hide or show a score",
+    description = "Hide a score from leaderboards and bests (or show it again). Only the owner of the score or an admin can do it"
+)]
+pub async fn update_score(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+    Json(update): Json<UpdateScore>,
+) -> Result<Json<ScoreSchema>, StatusCode> {
+    get_owned_score(&pool, &user, id).await?;
+
+    match Score::set_hidden(&pool, id, update.hidden).await {
+        Ok(Some(score)) => Ok(Json(score.to_schema())),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to update score {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/score/{id}",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Score ID")
+    ),
+    responses(
+        (status = 204, description = "Score deleted"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not the owner of the score"),
+        (status = 404, description = "Score not found")
+    ),
+    summary = "Delete a score",
+    description = "Delete a score and its ratings. Only the owner of the score or an admin can do it"
+)]
+pub async fn delete_score(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    get_owned_score(&pool, &user, id).await?;
+
+    match Score::delete(&pool, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to delete score {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/score/{id}/pin",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Score ID")
+    ),
+    responses(
+        (status = 204, description = "Score pinned"),
+        (status = 400, description = "Hidden scores cannot be pinned"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not the owner of the score"),
+        (status = 404, description = "Score not found"),
+        (status = 409, description = "Pinned scores limit reached")
+    ),
+    summary = "Pin a score",
+    description = "Pin a score on its player's profile. A player can pin up to 10 scores; pinning an already pinned score does nothing"
+)]
+pub async fn pin_score(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    let score = get_owned_score(&pool, &user, id).await?;
+    if score.hidden {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match UserScore::pin(&pool, score.user_id, id).await {
+        Ok(PinResult::Pinned | PinResult::AlreadyPinned) => Ok(StatusCode::NO_CONTENT),
+        Ok(PinResult::LimitReached) => {
+            info!("User {} already has {} pinned scores", score.user_id, MAX_PINNED_SCORES);
+            Err(StatusCode::CONFLICT)
+        }
+        Err(e) => {
+            error!("Failed to pin score {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/score/{id}/pin",
+    tag = "Score",
+    params(
+        ("id" = i32, Path, description = "Score ID")
+    ),
+    responses(
+        (status = 204, description = "Score unpinned"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not the owner of the score"),
+        (status = 404, description = "Score not found or not pinned")
+    ),
+    summary = "Unpin a score",
+    description = "Remove a score from its player's pinned scores"
+)]
+pub async fn unpin_score(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    get_owned_score(&pool, &user, id).await?;
+
+    match UserScore::unpin(&pool, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to unpin score {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/scores/snipes",
+    tag = "Score",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number (default: 1)"),
+        ("per_page" = Option<i64>, Query, description = "Items per page (default: 20, max: 50)")
+    ),
+    responses(
+        (status = 200, description = "Recent snipes", body = Vec<FirstPlaceChange>)
+    ),
+    summary = "Get recent snipes",
+    description = "Get the most recent first places taken from another player by a better score, on all beatmaps"
+)]
+pub async fn get_recent_snipes(
+    State(pool): State<PgPool>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Vec<FirstPlaceChange>>, StatusCode> {
+    let per_page = params.get_per_page();
+    let offset = (params.get_page() - 1) * per_page;
+
+    match FirstPlaceChange::get_recent_snipes(&pool, per_page, offset).await {
+        Ok(changes) => Ok(Json(changes)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
@@ -1,27 +1,97 @@
 use sqlx::PgPool;
-use crate::models::user::user::User;
+use crate::models::user::user::{AccountChanges, AccountUpdate, SimplfiedUser, User};
+use crate::auth::{hash_password, verify_password};
+use crate::models::user::follow::UserFollow;
+use crate::models::user::profile::UserProfile;
+use crate::models::user::rank_history::{UserRankHistory, UserRankHistorySchema};
+use crate::models::user::session::CurrentSession;
+use crate::models::score::score_rating::{RatingType, ScoreChokeSchema, ScoreRating, UserOverallRating, UserSkillProfile};
+use crate::models::score::skillset::{Skillsets, UserSkillset};
+use crate::models::score::first_place::{BeatmapFirstPlace, FirstPlaceChange};
+use crate::models::score::user_score::{UserScore, UserScoreSchema};
 use crate::models::common::PaginationParams;
+use crate::handlers::auth::send_email_verification;
+use crate::helpers::mailer::MailService;
+use crate::helpers::image::{process_profile_image, profile_image_path, store_profile_image, ImageError, ProfileImageKind};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 use axum::{response::Json, http::StatusCode};
-use axum::extract::{State, Query, Path};
+use axum::extract::{Extension, State, Query, Path};
+use axum_extra::extract::multipart::Multipart;
+use tracing::{error, warn};
 
 
+/// Construit le profil d'un utilisateur pour le mode demandé
+async fn user_profile(pool: &PgPool, user: Option<User>, params: &HistoryParams) -> Result<Json<UserProfile>, StatusCode> {
+    let mode = params.mode.unwrap_or(0);
+    if !(0..=3).contains(&mode) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user = user.ok_or(StatusCode::NOT_FOUND)?;
+
+    match UserProfile::build(pool, &user, mode).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/user/{id}",
     tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        HistoryParams
+    ),
     responses(
-        (status = 200, description = "User found", body = User),
+        (status = 200, description = "User found", body = UserProfile),
+        (status = 400, description = "Invalid mode"),
         (status = 404, description = "User not found")
     ),
-    summary = "Get user by id",
-    description = "Get a user by their id"
+    summary = "Get user profile by id",
+    description = "Get the profile of a user for a mode: pp, ranks, accuracy, play count, grade counts, skills, ranked match record, top plays, recent plays and first places"
 )]
-pub async fn get_user_by_id(State(pool): State<PgPool>, Path(id): Path<i32>) -> Result<Json<User>, StatusCode> {
-    match User::get_by_id(&pool, id).await {
-        Ok(user) => Ok(Json(user.unwrap())),
-        Err(_) => Err(StatusCode::NOT_FOUND)
-    }
-}   
+pub async fn get_user_by_id(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<UserProfile>, StatusCode> {
+    let user = User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    user_profile(&pool, user, &params).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/by-name/{username}",
+    tag = "User",
+    params(
+        ("username" = String, Path, description = "Username"),
+        HistoryParams
+    ),
+    responses(
+        (status = 200, description = "User found", body = UserProfile),
+        (status = 400, description = "Invalid mode"),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user profile by username",
+    description = "Get the profile of a user for a mode from their username. A username changed less than 90 days ago still leads to its user"
+)]
+pub async fn get_user_by_name(
+    State(pool): State<PgPool>,
+    Path(username): Path<String>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<UserProfile>, StatusCode> {
+    let user = User::get_by_current_or_previous_username(&pool, &username)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    user_profile(&pool, user, &params).await
+}
 
 #[utoipa::path(
     get,
@@ -46,3 +116,657 @@ pub async fn get_users(
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HistoryParams {
+    /// Game mode (0 = osu!, 1 = taiko, 2 = catch, 3 = mania), default: 0
+    pub mode: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/history",
+    tag = "User",
+    params(HistoryParams),
+    responses(
+        (status = 200, description = "Rank history found", body = Vec<UserRankHistorySchema>),
+        (status = 400, description = "Invalid mode"),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user rank history",
+    description = "Get the daily global rank, country rank, pp and accuracy snapshots of a user for a mode"
+)]
+pub async fn get_user_history(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<UserRankHistorySchema>>, StatusCode> {
+    let mode = params.mode.unwrap_or(0);
+    if !(0..=3).contains(&mode) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let history = UserRankHistory::get_by_user(&pool, id, mode)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(history.iter().map(|h| h.to_schema()).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/ratings/{rating_type}",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("rating_type" = String, Path, description = "Rating type name (pp, quaver, etterna)")
+    ),
+    responses(
+        (status = 200, description = "Overall rating computed", body = UserOverallRating),
+        (status = 404, description = "User or rating type not found")
+    ),
+    summary = "Get user overall rating",
+    description = "Get the overall rating of a user for a rating type, as the 0.95-weighted average of their 50 best ratings (one per beatmap)"
+)]
+pub async fn get_user_overall_rating(
+    State(pool): State<PgPool>,
+    Path((id, rating_type)): Path<(i32, String)>,
+) -> Result<Json<UserOverallRating>, StatusCode> {
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    RatingType::get_by_name(&pool, &rating_type)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let overall = ScoreRating::get_user_overall(&pool, id, &rating_type)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(overall))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/skillsets",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Skillsets found", body = Skillsets),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user Etterna skillsets",
+    description = "Get the Etterna-style skillset ratings of a user, aggregated from their mania scores. All values are 0 if the user has no rated mania score"
+)]
+pub async fn get_user_skillsets(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<Skillsets>, StatusCode> {
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let skillsets = UserSkillset::get_by_user(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(skillsets.map(|s| s.to_schema()).unwrap_or_default()))
+}
+
+#[derive(Debug, Deserialize, IntoParams, Validate)]
+pub struct ChokesParams {
+    /// Number of scores returned (default: 50, max: 100)
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/chokes",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ChokesParams
+    ),
+    responses(
+        (status = 200, description = "Chokes found", body = Vec<ScoreChokeSchema>),
+        (status = 400, description = "Invalid parameters"),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user chokes",
+    description = "Get the scores of a user that lost the most pp compared to a full combo, with their full combo and SS pp"
+)]
+pub async fn get_user_chokes(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<ChokesParams>,
+) -> Result<Json<Vec<ScoreChokeSchema>>, StatusCode> {
+    if params.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let chokes = ScoreRating::get_user_chokes(&pool, id, params.limit.unwrap_or(50))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(chokes.iter().map(|c| c.to_schema()).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/skills",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        HistoryParams
+    ),
+    responses(
+        (status = 200, description = "Skill profile computed", body = UserSkillProfile),
+        (status = 400, description = "Invalid mode"),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user skill profile",
+    description = "Get the aim, speed, accuracy and flashlight strength of a user for a mode, as the 0.95-weighted average of the pp components of their 100 best scores (one per beatmap)"
+)]
+pub async fn get_user_skill_profile(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<UserSkillProfile>, StatusCode> {
+    let mode = params.mode.unwrap_or(0);
+    if !(0..=3).contains(&mode) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let profile = ScoreRating::get_user_skill_profile(&pool, id, mode)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(profile))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/follow",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "ID of the user to follow")
+    ),
+    responses(
+        (status = 204, description = "User followed"),
+        (status = 400, description = "Cannot follow yourself"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Follow a user",
+    description = "Add a user to the requesting user's friends, used by friends leaderboards. Following an already followed user has no effect"
+)]
+pub async fn follow_user(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    if id == user.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    UserFollow::follow(&pool, user.id, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/user/{id}/follow",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "ID of the user to unfollow")
+    ),
+    responses(
+        (status = 204, description = "User unfollowed"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "User not followed")
+    ),
+    summary = "Unfollow a user",
+    description = "Remove a user from the requesting user's friends"
+)]
+pub async fn unfollow_user(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, StatusCode> {
+    match UserFollow::unfollow(&pool, user.id, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/following",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Followed users", body = Vec<SimplfiedUser>),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get followed users",
+    description = "Get the users followed by a user"
+)]
+pub async fn get_user_following(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<SimplfiedUser>>, StatusCode> {
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    match UserFollow::get_following(&pool, id).await {
+        Ok(users) => Ok(Json(users)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/followers",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Followers", body = Vec<SimplfiedUser>),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get followers",
+    description = "Get the users following a user"
+)]
+pub async fn get_user_followers(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<SimplfiedUser>>, StatusCode> {
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    match UserFollow::get_followers(&pool, id).await {
+        Ok(users) => Ok(Json(users)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/first-places",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("page" = Option<i64>, Query, description = "Page number (default: 1)"),
+        ("per_page" = Option<i64>, Query, description = "Items per page (default: 20, max: 50)")
+    ),
+    responses(
+        (status = 200, description = "Current first places", body = Vec<BeatmapFirstPlace>),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user first places",
+    description = "Get the beatmaps on which a user currently holds #1 of the score leaderboard, most recent first"
+)]
+pub async fn get_user_first_places(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Vec<BeatmapFirstPlace>>, StatusCode> {
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let per_page = params.get_per_page();
+    let offset = (params.get_page() - 1) * per_page;
+
+    match BeatmapFirstPlace::get_by_user(&pool, id, per_page, offset).await {
+        Ok(first_places) => Ok(Json(first_places)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/sniped",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("page" = Option<i64>, Query, description = "Page number (default: 1)"),
+        ("per_page" = Option<i64>, Query, description = "Items per page (default: 20, max: 50)")
+    ),
+    responses(
+        (status = 200, description = "First places lost", body = Vec<FirstPlaceChange>),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user sniped first places",
+    description = "Get the first places a user lost to a better score, most recent first"
+)]
+pub async fn get_user_sniped(
+    State(pool): State<PgPool>,
+    Path(id): Path<i32>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Vec<FirstPlaceChange>>, StatusCode> {
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let per_page = params.get_per_page();
+    let offset = (params.get_page() - 1) * per_page;
+
+    match FirstPlaceChange::get_sniped_from_user(&pool, id, per_page, offset).await {
+        Ok(changes) => Ok(Json(changes)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams, Validate)]
+pub struct UserScoresParams {
+    /// Game mode (0 = osu!, 1 = taiko, 2 = catch, 3 = mania), default: 0
+    #[validate(range(min = 0, max = 3))]
+    pub mode: Option<i32>,
+    /// Only scores played with exactly these mods
+    pub mods: Option<i32>,
+    /// Page number (default: 1)
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
+    /// Items per page (default: 20, max: 50)
+    #[validate(range(min = 1, max = 50))]
+    pub per_page: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/scores/{kind}",
+    tag = "User",
+    params(
+        ("id" = i32, Path, description = "User ID"),
+        ("kind" = String, Path, description = "\"best\" (by pp, one per beatmap), \"recent\", \"firsts\" (current #1s) or \"pinned\""),
+        UserScoresParams
+    ),
+    responses(
+        (status = 200, description = "Scores found", body = Vec<UserScoreSchema>),
+        (status = 400, description = "Invalid kind or parameters"),
+        (status = 404, description = "User not found")
+    ),
+    summary = "Get user scores",
+    description = "Get the best, recent, first place or pinned scores of a user for a mode, with their beatmap, beatmapset and pp. Hidden scores are not listed"
+)]
+pub async fn get_user_scores(
+    State(pool): State<PgPool>,
+    Path((id, kind)): Path<(i32, String)>,
+    Query(params): Query<UserScoresParams>,
+) -> Result<Json<Vec<UserScoreSchema>>, StatusCode> {
+    if params.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    User::get_by_id(&pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mode = params.mode.unwrap_or(0);
+    let per_page = params.per_page.unwrap_or(20);
+    let offset = (params.page.unwrap_or(1) - 1) * per_page;
+
+    let scores = match kind.as_str() {
+        "best" => UserScore::get_best(&pool, id, mode, params.mods, per_page, offset).await,
+        "recent" => UserScore::get_recent(&pool, id, mode, params.mods, per_page, offset).await,
+        "firsts" => UserScore::get_firsts(&pool, id, mode, params.mods, per_page, offset).await,
+        "pinned" => UserScore::get_pinned(&pool, id, mode, params.mods, per_page, offset).await,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    match scores {
+        Ok(scores) => Ok(Json(scores.iter().map(|s| s.to_schema()).collect())),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Fichier image envoyé en multipart
+#[derive(Debug, ToSchema)]
+#[allow(dead_code)]
+pub struct ProfileImageUpload {
+    /// PNG or JPEG image
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+/// Valide, stocke et associe une image de profil à l'utilisateur, puis supprime l'image précédente
+async fn upload_profile_image(
+    pool: &PgPool,
+    user: &User,
+    kind: ProfileImageKind,
+    mut multipart: Multipart,
+) -> Result<Json<User>, StatusCode> {
+    let mut data = None;
+    while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
+        if field.name() == Some("file") {
+            data = Some(field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+            break;
+        }
+    }
+    let data = data.ok_or(StatusCode::BAD_REQUEST)?;
+
+    // Décodage et redimensionnement coûteux : hors du runtime async
+    let image = tokio::task::spawn_blocking(move || process_profile_image(kind, &data))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| match e {
+            ImageError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ImageError::UnsupportedFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ImageError::Invalid => StatusCode::BAD_REQUEST,
+        })?;
+    let url = image.url(kind);
+
+    // Écriture du fichier et association sous le verrou de l'image : une suppression
+    // concurrente du même fichier attend la validation de la transaction
+    let mut tx = pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    User::lock_profile_image(&mut tx, &url)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    store_profile_image(kind, &image).await.map_err(|e| {
+        error!("Failed to store {:?} of user {}: {}", kind, user.id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let (updated, previous) = User::set_profile_image(&mut tx, user.id, kind, &url)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if let Some(previous) = previous.filter(|previous| *previous != url) {
+        if let Err(e) = delete_unused_profile_image(pool, kind, &previous).await {
+            warn!("Failed to delete previous {:?} {}: {}", kind, previous, e);
+        }
+    }
+
+    Ok(Json(updated))
+}
+
+/// Supprime le fichier d'une image de profil si plus aucun utilisateur ne la référence
+async fn delete_unused_profile_image(pool: &PgPool, kind: ProfileImageKind, url: &str) -> Result<(), String> {
+    let Some(path) = profile_image_path(kind, url) else {
+        return Ok(());
+    };
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    User::lock_profile_image(&mut tx, url).await.map_err(|e| e.to_string())?;
+    if !User::is_profile_image_used(&mut tx, url).await.map_err(|e| e.to_string())? {
+        tokio::fs::remove_file(&path).await.map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/user/me/avatar",
+    tag = "User",
+    request_body(content = ProfileImageUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Avatar updated", body = User),
+        (status = 400, description = "Missing or malformed file"),
+        (status = 401, description = "Not authenticated"),
+        (status = 413, description = "File larger than 1 MiB"),
+        (status = 415, description = "Not a PNG or JPEG image")
+    ),
+    summary = "Upload avatar",
+    description = "Upload a PNG or JPEG avatar (field \"file\", at most 1 MiB). The image is resized and center-cropped to 256x256, re-encoded without metadata, and the previous avatar is deleted"
+)]
+pub async fn upload_avatar(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    multipart: Multipart,
+) -> Result<Json<User>, StatusCode> {
+    upload_profile_image(&pool, &user, ProfileImageKind::Avatar, multipart).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/user/me/cover",
+    tag = "User",
+    request_body(content = ProfileImageUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Cover updated", body = User),
+        (status = 400, description = "Missing or malformed file"),
+        (status = 401, description = "Not authenticated"),
+        (status = 413, description = "File larger than 4 MiB"),
+        (status = 415, description = "Not a PNG or JPEG image")
+    ),
+    summary = "Upload cover",
+    description = "Upload a PNG or JPEG profile cover (field \"file\", at most 4 MiB). The image is resized and center-cropped to 2400x640, re-encoded without metadata, and the previous cover is deleted"
+)]
+pub async fn upload_cover(
+    State(pool): State<PgPool>,
+    Extension(user): Extension<User>,
+    multipart: Multipart,
+) -> Result<Json<User>, StatusCode> {
+    upload_profile_image(&pool, &user, ProfileImageKind::Cover, multipart).await
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdateAccountRequest {
+    /// New username (3 to 32 letters, digits, "_" or "-"), at most once every 30 days
+    #[validate(length(min = 3, max = 32))]
+    pub username: Option<String>,
+    /// New email, the account must be verified again
+    #[validate(email)]
+    pub email: Option<String>,
+    /// Country code (ISO 3166-1 alpha-2)
+    #[validate(length(equal = 2))]
+    pub country: Option<String>,
+    #[validate(length(min = 8, max = 128))]
+    pub new_password: Option<String>,
+    /// Required to change the email or the password
+    pub current_password: Option<String>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/user/me",
+    tag = "User",
+    request_body = UpdateAccountRequest,
+    responses(
+        (status = 200, description = "Account updated", body = User),
+        (status = 400, description = "Invalid fields, or current password missing"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Wrong current password"),
+        (status = 409, description = "Username or email already used"),
+        (status = 429, description = "Username changed less than 30 days ago")
+    ),
+    summary = "Update my account",
+    description = "Change the username, email, password or country of the authenticated user. Changing the email or the password requires the current password; a new email must be verified again and a verification link is sent to it. Changing the password signs out every other session. Previous usernames stay reserved for 90 days"
+)]
+pub async fn update_me(
+    State(pool): State<PgPool>,
+    Extension(mail): Extension<MailService>,
+    Extension(user): Extension<User>,
+    Extension(CurrentSession(session_id)): Extension<CurrentSession>,
+    Json(request): Json<UpdateAccountRequest>,
+) -> Result<Json<User>, StatusCode> {
+    if request.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Some(username) = &request.username {
+        if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    if request.email.is_some() || request.new_password.is_some() {
+        let current_password = request.current_password.as_deref().ok_or(StatusCode::BAD_REQUEST)?;
+        let valid = verify_password(current_password, &user.password_hash)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if !valid {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    let password_hash = match &request.new_password {
+        Some(password) => Some(hash_password(password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?),
+        None => None,
+    };
+
+    let changes = AccountChanges {
+        username: request.username,
+        email: request.email,
+        password_hash,
+        country: request.country.map(|country| country.to_uppercase()),
+    };
+
+    match User::update_account(&pool, user.id, session_id, changes).await {
+        Ok(AccountUpdate::Updated(updated)) => {
+            if updated.email != user.email {
+                if let Err(e) = send_email_verification(&pool, &mail, &updated).await {
+                    error!("Failed to issue verification token for user {}: {}", updated.id, e);
+                }
+            }
+            Ok(Json(*updated))
+        }
+        Ok(AccountUpdate::UsernameTaken | AccountUpdate::EmailTaken) => Err(StatusCode::CONFLICT),
+        Ok(AccountUpdate::UsernameCooldown(_)) => Err(StatusCode::TOO_MANY_REQUESTS),
+        Err(e) => {
+            error!("Failed to update account of user {}: {}", user.id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use rosu_pp::any::{DifficultyAttributes, Strains};
+use rosu_pp::catch::CatchDifficultyAttributes;
+use rosu_pp::model::beatmap::BeatmapAttributesBuilder;
+use rosu_pp::model::mode::GameMode;
+use rosu_pp::mania::ManiaDifficultyAttributes;
+use rosu_pp::osu::OsuDifficultyAttributes;
+use rosu_pp::taiko::TaikoDifficultyAttributes;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Version du calcul de difficulté, à incrémenter lors d'une mise à jour de rosu-pp
+/// modifiant les attributs : les entrées en cache des versions précédentes sont ignorées.
+pub const DIFFICULTY_CALCULATOR_VERSION: i32 = 1;
+
+/// Nombre de points des courbes de strain renvoyées au frontend
+pub const STRAIN_GRAPH_POINTS: usize = 200;
+
+const EZ: i32 = 2;
+const HR: i32 = 16;
+const DT: i32 = 64;
+const HT: i32 = 256;
+const NC: i32 = 512;
+/// Mode mania
+const MANIA: i32 = 3;
+/// Mods de nombre de touches en mania : 4K à 9K, puis 1K, 3K et 2K
+const KEY_MODS_MASK: i32 = 32768 | 65536 | 131072 | 262144 | 524288 | 16777216 | 67108864 | 134217728 | 268435456;
+/// Mods modifiant la difficulté : EZ, TD, HD, HR, DT, HT, NC, FL et nombre de touches
+const DIFFICULTY_MODS_MASK: i32 = EZ | 4 | 8 | HR | DT | HT | NC | 1024 | KEY_MODS_MASK;
+
+/// Ne garde que les mods qui influent sur la difficulté, NC étant traité comme DT
+///
+/// Les mods de touches sont conservés : ils changent le nombre de colonnes en mania.
+pub fn difficulty_mods(mods: i32) -> i32 {
+    let mut mods = mods & DIFFICULTY_MODS_MASK;
+    if mods & NC != 0 {
+        mods = (mods & !NC) | DT;
+    }
+    mods
+}
+
+/// Combinaison de mods impossible pour le mode de la beatmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidMods;
+
+/// Valide des mods reçus d'un client et les normalise avec [`difficulty_mods`]
+///
+/// Refuse les valeurs négatives, EZ avec HR, DT ou NC avec HT, plusieurs mods de touches,
+/// et les mods de touches hors mania : chaque combinaison distincte coûte un calcul et une
+/// entrée de cache, seules les combinaisons jouables sont acceptées.
+pub fn validate_mods(mods: i32, mode: i32) -> Result<i32, InvalidMods> {
+    if mods < 0 {
+        return Err(InvalidMods);
+    }
+
+    let mods = difficulty_mods(mods);
+    let keys = mods & KEY_MODS_MASK;
+    let conflicting = (mods & EZ != 0 && mods & HR != 0)
+        || (mods & DT != 0 && mods & HT != 0)
+        || keys.count_ones() > 1
+        || (keys != 0 && mode != MANIA);
+
+    if conflicting {
+        Err(InvalidMods)
+    } else {
+        Ok(mods)
+    }
+}
+
+/// Attributs de difficulté rosu-pp, sous une forme sérialisable pour le cache
+///
+/// Les types de rosu-pp n'implémentent pas serde : cette énumération en reprend
+/// tous les champs pour pouvoir reconstruire les attributs sans recalcul.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum CachedAttributes {
+    Osu {
+        aim: f64,
+        speed: f64,
+        flashlight: f64,
+        slider_factor: f64,
+        speed_note_count: f64,
+        aim_difficult_strain_count: f64,
+        speed_difficult_strain_count: f64,
+        ar: f64,
+        od: f64,
+        hp: f64,
+        n_circles: u32,
+        n_sliders: u32,
+        n_large_ticks: u32,
+        n_spinners: u32,
+        stars: f64,
+        max_combo: u32,
+    },
+    Taiko {
+        stamina: f64,
+        rhythm: f64,
+        color: f64,
+        peak: f64,
+        great_hit_window: f64,
+        ok_hit_window: f64,
+        mono_stamina_factor: f64,
+        stars: f64,
+        max_combo: u32,
+        is_convert: bool,
+    },
+    Catch {
+        stars: f64,
+        ar: f64,
+        n_fruits: u32,
+        n_droplets: u32,
+        n_tiny_droplets: u32,
+        is_convert: bool,
+    },
+    Mania {
+        stars: f64,
+        hit_window: f64,
+        n_objects: u32,
+        n_hold_notes: u32,
+        max_combo: u32,
+        is_convert: bool,
+    },
+}
+
+impl From<&DifficultyAttributes> for CachedAttributes {
+    fn from(attributes: &DifficultyAttributes) -> Self {
+        match attributes {
+            DifficultyAttributes::Osu(a) => Self::Osu {
+                aim: a.aim,
+                speed: a.speed,
+                flashlight: a.flashlight,
+                slider_factor: a.slider_factor,
+                speed_note_count: a.speed_note_count,
+                aim_difficult_strain_count: a.aim_difficult_strain_count,
+                speed_difficult_strain_count: a.speed_difficult_strain_count,
+                ar: a.ar,
+                od: a.od,
+                hp: a.hp,
+                n_circles: a.n_circles,
+                n_sliders: a.n_sliders,
+                n_large_ticks: a.n_large_ticks,
+                n_spinners: a.n_spinners,
+                stars: a.stars,
+                max_combo: a.max_combo,
+            },
+            DifficultyAttributes::Taiko(a) => Self::Taiko {
+                stamina: a.stamina,
+                rhythm: a.rhythm,
+                color: a.color,
+                peak: a.peak,
+                great_hit_window: a.great_hit_window,
+                ok_hit_window: a.ok_hit_window,
+                mono_stamina_factor: a.mono_stamina_factor,
+                stars: a.stars,
+                max_combo: a.max_combo,
+                is_convert: a.is_convert,
+            },
+            DifficultyAttributes::Catch(a) => Self::Catch {
+                stars: a.stars,
+                ar: a.ar,
+                n_fruits: a.n_fruits,
+                n_droplets: a.n_droplets,
+                n_tiny_droplets: a.n_tiny_droplets,
+                is_convert: a.is_convert,
+            },
+            DifficultyAttributes::Mania(a) => Self::Mania {
+                stars: a.stars,
+                hit_window: a.hit_window,
+                n_objects: a.n_objects,
+                n_hold_notes: a.n_hold_notes,
+                max_combo: a.max_combo,
+                is_convert: a.is_convert,
+            },
+        }
+    }
+}
+
+impl From<&CachedAttributes> for DifficultyAttributes {
+    fn from(cached: &CachedAttributes) -> Self {
+        match *cached {
+            CachedAttributes::Osu {
+                aim, speed, flashlight, slider_factor, speed_note_count,
+                aim_difficult_strain_count, speed_difficult_strain_count,
+                ar, od, hp, n_circles, n_sliders, n_large_ticks, n_spinners, stars, max_combo,
+            } => Self::Osu(OsuDifficultyAttributes {
+                aim, speed, flashlight, slider_factor, speed_note_count,
+                aim_difficult_strain_count, speed_difficult_strain_count,
+                ar, od, hp, n_circles, n_sliders, n_large_ticks, n_spinners, stars, max_combo,
+            }),
+            CachedAttributes::Taiko {
+                stamina, rhythm, color, peak, great_hit_window, ok_hit_window,
+                mono_stamina_factor, stars, max_combo, is_convert,
+            } => Self::Taiko(TaikoDifficultyAttributes {
+                stamina, rhythm, color, peak, great_hit_window, ok_hit_window,
+                mono_stamina_factor, stars, max_combo, is_convert,
+            }),
+            CachedAttributes::Catch { stars, ar, n_fruits, n_droplets, n_tiny_droplets, is_convert } => {
+                Self::Catch(CatchDifficultyAttributes { stars, ar, n_fruits, n_droplets, n_tiny_droplets, is_convert })
+            }
+            CachedAttributes::Mania { stars, hit_window, n_objects, n_hold_notes, max_combo, is_convert } => {
+                Self::Mania(ManiaDifficultyAttributes { stars, hit_window, n_objects, n_hold_notes, max_combo, is_convert })
+            }
+        }
+    }
+}
+
+/// Résultat complet d'un calcul de difficulté pour une combinaison de mods
+#[derive(Debug, Clone)]
+pub struct ComputedDifficulty {
+    pub attributes: DifficultyAttributes,
+    /// AR, OD, HP et CS après application des mods
+    pub ar: f64,
+    pub od: f64,
+    pub hp: f64,
+    pub cs: f64,
+}
+
+/// AR, OD, CS, HP et vitesse de lecture d'une beatmap après application des mods
+#[derive(Debug, Clone, Copy)]
+pub struct ModdedAttributes {
+    pub ar: f64,
+    pub od: f64,
+    pub cs: f64,
+    pub hp: f64,
+    pub clock_rate: f64,
+}
+
+/// Applique des mods aux attributs bruts d'une beatmap, sans avoir besoin du fichier .osu
+pub fn modded_attributes(mode: i32, ar: f64, od: f64, cs: f64, hp: f64, mods: i32) -> ModdedAttributes {
+    let attributes = BeatmapAttributesBuilder::new()
+        .mode(GameMode::from(mode as u8), false)
+        .ar(ar as f32, false)
+        .od(od as f32, false)
+        .cs(cs as f32, false)
+        .hp(hp as f32, false)
+        .mods(difficulty_mods(mods) as u32)
+        .build();
+
+    ModdedAttributes {
+        ar: attributes.ar,
+        od: attributes.od,
+        cs: attributes.cs,
+        hp: attributes.hp,
+        clock_rate: attributes.clock_rate,
+    }
+}
+
+/// Calcule les attributs de difficulté d'une beatmap pour des mods donnés
+pub fn compute_difficulty(map: &rosu_pp::Beatmap, mods: i32) -> ComputedDifficulty {
+    let mods = difficulty_mods(mods) as u32;
+    let attributes = rosu_pp::Difficulty::new().mods(mods).calculate(map);
+    let map_attributes = map.attributes().mods(mods).build();
+
+    ComputedDifficulty {
+        attributes,
+        ar: map_attributes.ar,
+        od: map_attributes.od,
+        hp: map_attributes.hp,
+        cs: map_attributes.cs,
+    }
+}
+
+/// Courbe de strain d'une compétence
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StrainSeries {
+    /// aim, aim_no_sliders, speed, flashlight, color, rhythm, stamina, single_color_stamina, movement ou strain
+    pub skill: String,
+    pub values: Vec<f64>,
+}
+
+/// Courbes de strain d'une beatmap, sous-échantillonnées à au plus [`STRAIN_GRAPH_POINTS`] points
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StrainGraph {
+    /// Durée couverte par chaque point, en ms de la beatmap (sans l'accélération de DT/HT)
+    pub point_len: f64,
+    pub series: Vec<StrainSeries>,
+}
+
+/// Calcule les courbes de strain d'une beatmap pour des mods donnés
+pub fn compute_strains(map: &rosu_pp::Beatmap, mods: i32) -> StrainGraph {
+    let mods = difficulty_mods(mods) as u32;
+    let strains = rosu_pp::Difficulty::new().mods(mods).strains(map);
+    // Les sections de rosu-pp sont en temps joué : on les ramène au temps de la beatmap
+    let clock_rate = map.attributes().mods(mods).build().clock_rate;
+    let section_len = strains.section_len() * clock_rate;
+
+    let series: Vec<(&str, Vec<f64>)> = match strains {
+        Strains::Osu(s) => vec![
+            ("aim", s.aim),
+            ("aim_no_sliders", s.aim_no_sliders),
+            ("speed", s.speed),
+            ("flashlight", s.flashlight),
+        ],
+        Strains::Taiko(s) => vec![
+            ("color", s.color),
+            ("rhythm", s.rhythm),
+            ("stamina", s.stamina),
+            ("single_color_stamina", s.single_color_stamina),
+        ],
+        Strains::Catch(s) => vec![("movement", s.movement)],
+        Strains::Mania(s) => vec![("strain", s.strains)],
+    };
+
+    let sections = series.iter().map(|(_, values)| values.len()).max().unwrap_or(0);
+    let points = sections.min(STRAIN_GRAPH_POINTS);
+    let point_len = if points == 0 { section_len } else { section_len * sections as f64 / points as f64 };
+
+    StrainGraph {
+        point_len,
+        series: series.into_iter()
+            .map(|(skill, values)| StrainSeries { skill: skill.to_string(), values: downsample(&values, points) })
+            .collect(),
+    }
+}
+
+/// Réduit une courbe à `points` valeurs en gardant le pic de chaque intervalle
+fn downsample(values: &[f64], points: usize) -> Vec<f64> {
+    if values.len() <= points {
+        return values.to_vec();
+    }
+
+    (0..points)
+        .map(|i| {
+            let start = i * values.len() / points;
+            let end = ((i + 1) * values.len() / points).max(start + 1);
+            values[start..end].iter().copied().fold(0.0, f64::max)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_mods_maps_nightcore_to_double_time() {
+        // NC est toujours envoyé avec DT par le client, mais pas forcément par l'API
+        assert_eq!(difficulty_mods(NC), DT);
+        assert_eq!(difficulty_mods(NC | DT), DT);
+        assert_eq!(difficulty_mods(NC | DT | HR), DT | HR);
+        assert_eq!(difficulty_mods(DT), difficulty_mods(NC | DT));
+    }
+
+    #[test]
+    fn difficulty_mods_drops_mods_without_difficulty_impact() {
+        // NF (1), SD (32) et PF (16384) ne changent pas la difficulté
+        assert_eq!(difficulty_mods(1 | 32 | 16384 | HR), HR);
+        assert_eq!(difficulty_mods(32768), 32768);
+    }
+
+    #[test]
+    fn validate_mods_normalizes_nightcore() {
+        assert_eq!(validate_mods(NC | DT, 0), Ok(DT));
+        assert_eq!(validate_mods(NC | HT, 0), Err(InvalidMods));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::helpers::mania::{group_chords, key_count, parse_notes, ManiaNote};
+use crate::helpers::pp::BeatmapFile;
+use crate::helpers::rating::{RatingCalculator, RatingResult};
+use crate::models::score::score::{Score, ScoreStatistics};
+use crate::models::score::skillset::{BeatmapSkillset, Skillsets, UserSkillset};
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+/// Nom du rating_type correspondant
+pub const ETTERNA_RATING_TYPE: &str = "etterna";
+
+/// Mods modifiant la vitesse de lecture (DT, HT, NC)
+pub const RATE_MODS_MASK: i32 = 64 | 256 | 512;
+
+/// Durée d'un intervalle d'analyse en ms
+const INTERVAL_LEN: f64 = 500.0;
+/// Facteur de calibration pour ramener les valeurs à l'échelle des MSD Etterna
+const MSD_SCALE: f64 = 3.0;
+/// Précision de référence d'Etterna : un MSD correspond à un score à 93%
+const REFERENCE_WIFE: f64 = 0.93;
+
+/// Écarts représentatifs (en ms) de chaque jugement en fenêtres J4,
+/// pris au milieu de la fenêtre correspondante
+const MARVELOUS_DEVIATION: f64 = 11.25;
+const PERFECT_DEVIATION: f64 = 33.75;
+const GREAT_DEVIATION: f64 = 67.5;
+const GOOD_DEVIATION: f64 = 112.5;
+const BAD_DEVIATION: f64 = 157.5;
+const MISS_WEIGHT: f64 = -5.5;
+
+/// Approximation de la fonction d'erreur (Abramowitz & Stegun 7.1.26)
+pub fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let y = 1.0
+        - (((((1.061_405_429 * t - 1.453_152_027) * t) + 1.421_413_741) * t - 0.284_496_736) * t
+            + 0.254_829_592)
+            * t
+            * (-x * x).exp();
+    sign * y
+}
+
+/// Fonction d'erreur complémentaire
+pub fn erfc(x: f64) -> f64 {
+    1.0 - erf(x)
+}
+
+/// Points Wife3 (sur 2) d'une note frappée avec un écart de `deviation` ms (timing scale J4)
+pub fn wife3_points(deviation: f64) -> f64 {
+    let ridic = 5.0;
+    let max_boo_weight = 180.0;
+    let zero = 65.0;
+    let dev = 22.7;
+
+    if deviation <= ridic {
+        2.0
+    } else if deviation <= zero {
+        2.0 * erf((zero - deviation) / dev)
+    } else if deviation <= max_boo_weight {
+        (deviation - zero) * MISS_WEIGHT / (max_boo_weight - zero)
+    } else {
+        MISS_WEIGHT
+    }
+}
+
+/// Précision Wife3 (entre 0 et 1) estimée à partir des jugements osu!mania
+///
+/// Les fichiers scores.db ne contiennent pas les écarts de chaque note : chaque jugement
+/// est donc converti en un écart représentatif avant d'appliquer la courbe Wife3.
+pub fn wife3_accuracy(statistics: &ScoreStatistics) -> f64 {
+    let judgements = [
+        (statistics.count_geki, wife3_points(MARVELOUS_DEVIATION)),
+        (statistics.count_300, wife3_points(PERFECT_DEVIATION)),
+        (statistics.count_katu, wife3_points(GREAT_DEVIATION)),
+        (statistics.count_100, wife3_points(GOOD_DEVIATION)),
+        (statistics.count_50, wife3_points(BAD_DEVIATION)),
+        (statistics.count_miss, MISS_WEIGHT),
+    ];
+
+    let total: i32 = judgements.iter().map(|(count, _)| count).sum();
+    if total <= 0 {
+        return 0.0;
+    }
+
+    let points: f64 = judgements.iter().map(|(count, points)| *count as f64 * points).sum();
+    (points / (total as f64 * 2.0)).max(0.0)
+}
+
+/// Facteur appliqué au MSD pour obtenir le SSR d'un score selon sa précision Wife3
+pub fn wife_scale(wife: f64) -> f64 {
+    (1.0 + 2.0 * (wife - REFERENCE_WIFE)).max(0.0)
+}
+
+/// Calcule les skillsets (MSD) d'une beatmap mania
+pub fn calculate_msd(notes: &[ManiaNote], keys: usize) -> Skillsets {
+    let Some(last) = notes.last() else { return Skillsets::default() };
+    let interval_count = (last.time / INTERVAL_LEN).floor() as usize + 1;
+    let interval_of = |time: f64| ((time / INTERVAL_LEN).floor().max(0.0) as usize).min(interval_count - 1);
+
+    let mut intervals = vec![IntervalStats::default(); interval_count];
+
+    // Composition des accords et régularité du rythme
+    let chords = group_chords(notes);
+    let mut previous_time: Option<f64> = None;
+    for chord in &chords {
+        let stats = &mut intervals[interval_of(chord[0].time)];
+        stats.rows += 1;
+        stats.notes += chord.len();
+        match chord.len() {
+            1 => stats.singles += 1,
+            2 => stats.jumps += 1,
+            _ => stats.hands += 1,
+        }
+        if let Some(previous) = previous_time {
+            stats.deltas.push(chord[0].time - previous);
+        }
+        previous_time = Some(chord[0].time);
+    }
+
+    // Vitesse par main : nombre d'actions (accords d'une main) par seconde
+    for left in [true, false] {
+        let hand_notes: Vec<ManiaNote> = notes.iter()
+            .filter(|note| if left { note.column * 2 < keys } else { note.column * 2 + 1 >= keys })
+            .copied()
+            .collect();
+
+        let mut actions = vec![0_usize; interval_count];
+        for chord in group_chords(&hand_notes) {
+            actions[interval_of(chord[0].time)] += 1;
+        }
+        for (stats, count) in intervals.iter_mut().zip(actions) {
+            stats.hand_speed = stats.hand_speed.max(count as f64 * 1000.0 / INTERVAL_LEN);
+        }
+    }
+
+    // Vitesse des jacks : écart minimal entre deux notes d'une même colonne
+    let mut last_in_column: Vec<Option<f64>> = vec![None; keys];
+    for note in notes {
+        if let Some(previous) = last_in_column[note.column] {
+            let delta = (note.time - previous).max(25.0);
+            let stats = &mut intervals[interval_of(note.time)];
+            stats.jack_speed = stats.jack_speed.max(1000.0 / delta);
+        }
+        last_in_column[note.column] = Some(note.time);
+    }
+
+    let mut stream = Vec::new();
+    let mut jumpstream = Vec::new();
+    let mut handstream = Vec::new();
+    let mut jackspeed = Vec::new();
+    let mut chordjack = Vec::new();
+    let mut technical = Vec::new();
+
+    for stats in intervals.iter().filter(|stats| stats.rows > 0) {
+        let rows = stats.rows as f64;
+        let single_ratio = stats.singles as f64 / rows;
+        let jump_ratio = stats.jumps as f64 / rows;
+        let hand_ratio = stats.hands as f64 / rows;
+        let chord_size = stats.notes as f64 / rows;
+        let base = stats.hand_speed;
+
+        stream.push(base * (0.7 + 0.3 * single_ratio));
+        jumpstream.push(base * if jump_ratio > 0.0 { 1.0 + 0.35 * jump_ratio } else { 0.85 });
+        handstream.push(base * if hand_ratio > 0.0 { 1.0 + 0.5 * hand_ratio } else { 0.85 });
+        jackspeed.push(stats.jack_speed * 0.55);
+        chordjack.push(stats.jack_speed * 0.5 * (1.0 + 0.3 * (chord_size - 1.0)));
+        technical.push(base * (0.8 + 0.4 * stats.irregularity()));
+    }
+
+    let stream_like: Vec<f64> = stream.iter().zip(&jumpstream).zip(&handstream)
+        .map(|((s, js), hs)| s.max(*js).max(*hs))
+        .collect();
+    let minutes = last.time / 60_000.0;
+    let stamina = mean(&stream_like) * (0.85 + 0.1 * (1.0 + minutes).ln()) * MSD_SCALE;
+
+    let mut skillsets = Skillsets {
+        overall: 0.0,
+        stream: power_mean(&stream) * MSD_SCALE,
+        jumpstream: power_mean(&jumpstream) * MSD_SCALE,
+        handstream: power_mean(&handstream) * MSD_SCALE,
+        stamina,
+        jackspeed: power_mean(&jackspeed) * MSD_SCALE,
+        chordjack: power_mean(&chordjack) * MSD_SCALE,
+        technical: power_mean(&technical) * MSD_SCALE,
+    };
+    skillsets.overall = skillsets.skills().iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    skillsets
+}
+
+/// Calcule les skillsets d'une beatmap mania pour des mods donnés
+pub async fn calculate_msd_for_beatmap(file: &BeatmapFile<'_>, mods: i32) -> Result<Skillsets, String> {
+    let map = file.get().await?;
+    let notes = parse_notes(map, (mods & RATE_MODS_MASK) as u32)?;
+    Ok(calculate_msd(&notes, key_count(map)))
+}
+
+/// Agrège une liste de SSR en un rating joueur, comme le fait Etterna
+///
+/// Cherche le rating pour lequel la somme des contributions des scores au-dessus
+/// dépasse 2^(rating / 10), en affinant la résolution à chaque itération.
+pub fn aggregate_ssrs(ssrs: &[f64]) -> f64 {
+    if ssrs.is_empty() {
+        return 0.0;
+    }
+
+    let mut rating = 0.0;
+    let mut resolution = 10.24;
+
+    for _ in 0..11 {
+        loop {
+            rating += resolution;
+            if rating > 100.0 {
+                break;
+            }
+            let sum: f64 = ssrs.iter()
+                .map(|ssr| (2.0 / erfc(0.1 * (ssr - rating)) - 2.0).max(0.0))
+                .sum();
+            if 2f64.powf(rating * 0.1) >= sum {
+                break;
+            }
+        }
+        rating -= resolution;
+        resolution /= 2.0;
+    }
+
+    rating + 2.0 * resolution
+}
+
+/// Calculateur du SSR Etterna, pour les scores osu!mania
+///
+/// Les MSD des beatmaps sont mis en cache dans `beatmap_skillset`, et les skillsets
+/// du joueur sont recalculés après chaque nouveau score.
+pub struct EtternaCalculator {
+    pool: PgPool,
+}
+
+impl EtternaCalculator {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RatingCalculator for EtternaCalculator {
+    fn name(&self) -> &'static str {
+        ETTERNA_RATING_TYPE
+    }
+
+    fn modes(&self) -> &'static [i32] {
+        &[3]
+    }
+
+    fn version(&self) -> i32 {
+        1
+    }
+
+    async fn calculate(&self, score: &Score, file: &BeatmapFile<'_>) -> Result<RatingResult, String> {
+        let msd = BeatmapSkillset::get_or_calculate(&self.pool, file, score.mods)
+            .await
+            .map_err(|e| e.to_string())?
+            .skillsets.0;
+
+        let wife = wife3_accuracy(&score.statistics);
+        Ok(RatingResult {
+            rating: msd.overall * wife_scale(wife),
+            max_rating: Some(msd.overall * wife_scale(1.0)),
+            fc_rating: None,
+            attributes: None,
+        })
+    }
+
+    async fn after_store(&self, score: &Score) -> Result<(), String> {
+        let mut conn = self.pool.acquire().await.map_err(|e| e.to_string())?;
+        UserSkillset::recalculate(&mut conn, score.user_id)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct IntervalStats {
+    rows: usize,
+    notes: usize,
+    singles: usize,
+    jumps: usize,
+    hands: usize,
+    hand_speed: f64,
+    jack_speed: f64,
+    deltas: Vec<f64>,
+}
+
+impl IntervalStats {
+    /// Coefficient de variation des écarts entre accords, borné à 1
+    fn irregularity(&self) -> f64 {
+        if self.deltas.len() < 2 {
+            return 0.0;
+        }
+        let average = mean(&self.deltas);
+        if average <= 0.0 {
+            return 0.0;
+        }
+        let variance = self.deltas.iter().map(|d| (d - average).powi(2)).sum::<f64>() / self.deltas.len() as f64;
+        (variance.sqrt() / average).min(1.0)
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Moyenne d'ordre 3 : favorise les sections difficiles sans se limiter au pic
+fn power_mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    (values.iter().map(|v| v.powi(3)).sum::<f64>() / values.len() as f64).cbrt()
+}
@@ -0,0 +1,174 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Dossier servi sous `/public`
+const PUBLIC_DIR: &str = "public";
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Dimensions maximales d'une image envoyée, avant redimensionnement
+const MAX_SOURCE_DIMENSION: u32 = 8192;
+/// Mémoire maximale allouée au décodage d'une image envoyée
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+/// Qualité des JPEG réencodés
+const JPEG_QUALITY: u8 = 90;
+
+/// Type d'image de profil, avec sa limite de poids et ses dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileImageKind {
+    Avatar,
+    Cover,
+}
+
+impl ProfileImageKind {
+    /// Sous-dossier de `public/` où sont stockées les images
+    pub fn directory(&self) -> &'static str {
+        match self {
+            Self::Avatar => "avatars",
+            Self::Cover => "covers",
+        }
+    }
+
+    /// Poids maximum du fichier envoyé, en octets
+    pub fn max_bytes(&self) -> usize {
+        match self {
+            Self::Avatar => 1024 * 1024,
+            Self::Cover => 4 * 1024 * 1024,
+        }
+    }
+
+    /// Dimensions standard (largeur, hauteur) auxquelles les images sont recadrées
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Avatar => (256, 256),
+            Self::Cover => (2400, 640),
+        }
+    }
+
+    /// Préfixe des URLs des images de ce type
+    pub fn url_prefix(&self) -> String {
+        format!("/{}/{}/", PUBLIC_DIR, self.directory())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// Fichier plus lourd que la limite du type d'image
+    TooLarge,
+    /// Ni PNG ni JPEG
+    UnsupportedFormat,
+    /// Fichier tronqué, mal formé ou trop grand pour être décodé
+    Invalid,
+}
+
+/// Image validée, recadrée et réencodée sans métadonnées, prête à être stockée
+#[derive(Debug)]
+pub struct ProcessedImage {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl ProcessedImage {
+    /// Nom du fichier, dérivé du contenu : deux envois identiques partagent le même fichier
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", hex::encode(Sha256::digest(&self.data)), self.format.extension())
+    }
+
+    /// URL publique de l'image une fois stockée
+    pub fn url(&self, kind: ProfileImageKind) -> String {
+        format!("{}{}", kind.url_prefix(), self.file_name())
+    }
+}
+
+/// Valide une image de profil, la recadre aux dimensions standard et la réencode
+///
+/// Seuls PNG et JPEG sont acceptés, d'après leur signature et non d'après le type
+/// annoncé par le client. L'image est redimensionnée pour couvrir les dimensions du type
+/// puis recadrée au centre ; l'orientation EXIF est appliquée avant. Le réencodage
+/// (PNG en PNG, JPEG en JPEG) ne conserve aucune métadonnée.
+pub fn process_profile_image(kind: ProfileImageKind, data: &[u8]) -> Result<ProcessedImage, ImageError> {
+    if data.len() > kind.max_bytes() {
+        return Err(ImageError::TooLarge);
+    }
+
+    let (format, decoded_format) = if data.starts_with(PNG_SIGNATURE) {
+        (ImageFormat::Png, image::ImageFormat::Png)
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        (ImageFormat::Jpeg, image::ImageFormat::Jpeg)
+    } else {
+        return Err(ImageError::UnsupportedFormat);
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::with_format(Cursor::new(data), decoded_format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder().map_err(|_| ImageError::Invalid)?;
+    let orientation = decoder.orientation().map_err(|_| ImageError::Invalid)?;
+    let mut decoded = DynamicImage::from_decoder(decoder).map_err(|_| ImageError::Invalid)?;
+    decoded.apply_orientation(orientation);
+
+    let (width, height) = kind.dimensions();
+    let resized = decoded.resize_to_fill(width, height, FilterType::Lanczos3);
+
+    let mut output = Vec::new();
+    match format {
+        ImageFormat::Png => resized.write_with_encoder(PngEncoder::new(&mut output)),
+        // Le JPEG n'a pas de canal alpha
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(resized.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)),
+    }
+    .map_err(|_| ImageError::Invalid)?;
+
+    Ok(ProcessedImage { format, width, height, data: output })
+}
+
+/// Enregistre une image de profil sous `public/`, à l'adresse de [`ProcessedImage::url`]
+pub async fn store_profile_image(kind: ProfileImageKind, image: &ProcessedImage) -> std::io::Result<()> {
+    let directory = Path::new(PUBLIC_DIR).join(kind.directory());
+    tokio::fs::create_dir_all(&directory).await?;
+
+    let file_name = image.file_name();
+    let path = directory.join(&file_name);
+    if !tokio::fs::try_exists(&path).await? {
+        // Écriture dans un fichier temporaire puis renommage : un fichier servi n'est jamais partiel
+        let temp_path = directory.join(format!("{}.tmp", file_name));
+        tokio::fs::write(&temp_path, &image.data).await?;
+        tokio::fs::rename(&temp_path, &path).await?;
+    }
+
+    Ok(())
+}
+
+/// Chemin local d'une image de profil à partir de son URL, `None` si elle n'est pas gérée par le serveur
+pub fn profile_image_path(kind: ProfileImageKind, url: &str) -> Option<PathBuf> {
+    let file_name = url.strip_prefix(&kind.url_prefix())?;
+    if file_name.is_empty() || file_name.contains('/') || file_name.contains("..") {
+        return None;
+    }
+    Some(Path::new(PUBLIC_DIR).join(kind.directory()).join(file_name))
+}
@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::config::{MailerConfig, SmtpConfig};
+
+/// Délai maximum de connexion au serveur SMTP
+const SMTP_CONNECT_TIMEOUT_SECONDS: u64 = 10;
+/// Délai maximum d'un envoi SMTP complet, connexion comprise : le transport asynchrone
+/// de lettre ne limite que la connexion, pas les lectures et écritures
+const SMTP_SEND_TIMEOUT_SECONDS: u64 = 30;
+
+/// Email en texte brut
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl EmailMessage {
+    /// Message lettre, avec validation des adresses et encodage des en-têtes
+    pub fn to_message(&self) -> Result<Message, String> {
+        let from: Mailbox = self.from.parse().map_err(|e| format!("Expéditeur invalide: {}", e))?;
+        let to: Mailbox = self.to.parse().map_err(|e| format!("Destinataire invalide: {}", e))?;
+
+        Message::builder()
+            .from(from)
+            .to(to)
+            .subject(self.subject.clone())
+            .header(ContentType::TEXT_PLAIN)
+            .body(self.body.clone())
+            .map_err(|e| format!("Email invalide: {}", e))
+    }
+}
+
+/// Transport d'emails
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<(), String>;
+}
+
+/// Écrit chaque email dans un fichier `.eml` du dossier de spool, pour le développement et les tests
+pub struct FileMailer {
+    directory: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        let content = message.to_message()?.formatted();
+        tokio::fs::create_dir_all(&self.directory)
+            .await
+            .map_err(|e| format!("Impossible de créer le dossier de spool: {}", e))?;
+
+        let file_name = format!("{}-{}.eml", chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"), uuid::Uuid::new_v4());
+        tokio::fs::write(self.directory.join(file_name), content)
+            .await
+            .map_err(|e| format!("Impossible d'écrire l'email: {}", e))
+    }
+}
+
+/// Envoie les emails via un serveur SMTP, avec STARTTLS ou TLS implicite
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    /// Construit le transport ; refuse des identifiants sans chiffrement, sauf `allow_insecure_auth`
+    pub fn new(config: SmtpConfig) -> Result<Self, String> {
+        let builder = match config.security.as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| format!("Configuration SMTP invalide: {}", e))?,
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|e| format!("Configuration SMTP invalide: {}", e))?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            other => return Err(format!("Sécurité SMTP inconnue: {}", other)),
+        };
+        let mut builder = builder
+            .port(config.port)
+            .timeout(Some(Duration::from_secs(SMTP_CONNECT_TIMEOUT_SECONDS)));
+
+        if let (Some(username), Some(password)) = (config.username, config.password) {
+            if config.security == "none" && !config.allow_insecure_auth {
+                return Err("Identifiants SMTP refusés sans chiffrement (security = \"none\") : activer allow_insecure_auth".to_string());
+            }
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self { transport: builder.build() })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        let send = self.transport.send(message.to_message()?);
+        match tokio::time::timeout(Duration::from_secs(SMTP_SEND_TIMEOUT_SECONDS), send).await {
+            Ok(result) => result.map(|_| ()).map_err(|e| format!("Envoi SMTP impossible: {}", e)),
+            Err(_) => Err("Envoi SMTP impossible: délai dépassé".to_string()),
+        }
+    }
+}
+
+/// Envoi des emails de l'application, partagé entre les handlers via une `Extension`
+#[derive(Clone)]
+pub struct MailService {
+    mailer: Arc<dyn Mailer>,
+    from: String,
+    frontend_url: String,
+}
+
+impl MailService {
+    pub fn new(mailer: Arc<dyn Mailer>, from: String, frontend_url: String) -> Self {
+        Self { mailer, from, frontend_url }
+    }
+
+    /// Construit le service à partir de la configuration
+    pub fn from_config(config: &MailerConfig) -> Result<Self, String> {
+        let mailer: Arc<dyn Mailer> = match config.transport.as_str() {
+            "smtp" => {
+                let smtp = config.smtp.clone().ok_or("Section [mailer.smtp] manquante")?;
+                Arc::new(SmtpMailer::new(smtp)?)
+            }
+            "file" => Arc::new(FileMailer::new(config.spool_dir.clone().unwrap_or_else(|| "mail_spool".to_string()))),
+            other => return Err(format!("Transport d'email inconnu: {}", other)),
+        };
+
+        Ok(Self::new(mailer, config.from.clone(), config.frontend_url.trim_end_matches('/').to_string()))
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), String> {
+        self.mailer.send(&EmailMessage {
+            from: self.from.clone(),
+            to: to.to_string(),
+            subject: subject.to_string(),
+            body,
+        }).await
+    }
+
+    /// Envoie le lien de vérification de l'adresse email
+    pub async fn send_email_verification(&self, to: &str, username: &str, token: &str) -> Result<(), String> {
+        let link = format!("{}/verify-email?token={}", self.frontend_url, token);
+        let body = format!(
+            "Bonjour {},\n\nConfirme ton adresse email en ouvrant ce lien :\n{}\n\nCe lien expire dans 24 heures. Si tu n'es pas à l'origine de cette demande, ignore cet email.\n",
+            username, link
+        );
+        self.send(to, "Vérification de ton adresse email", body).await
+    }
+
+    /// Envoie le lien de réinitialisation du mot de passe
+    pub async fn send_password_reset(&self, to: &str, username: &str, token: &str) -> Result<(), String> {
+        let link = format!("{}/reset-password?token={}", self.frontend_url, token);
+        let body = format!(
+            "Bonjour {},\n\nUne réinitialisation du mot de passe de ton compte a été demandée. Choisis un nouveau mot de passe en ouvrant ce lien :\n{}\n\nCe lien expire dans 1 heure et ne peut être utilisé qu'une fois. Si tu n'es pas à l'origine de cette demande, ignore cet email : ton mot de passe reste inchangé.\n",
+            username, link
+        );
+        self.send(to, "Réinitialisation de ton mot de passe", body).await
+    }
+}
@@ -0,0 +1,65 @@
+use rosu_pp::model::hit_object::HitObjectKind;
+use rosu_pp::model::mode::GameMode;
+
+/// Une note de mania, avec ses temps déjà ajustés au taux de lecture (DT/HT)
+#[derive(Debug, Clone, Copy)]
+pub struct ManiaNote {
+    pub time: f64,
+    pub end_time: f64,
+    pub column: usize,
+}
+
+/// Nombre de colonnes d'une beatmap mania
+pub fn key_count(map: &rosu_pp::Beatmap) -> usize {
+    (map.cs.round() as usize).max(1)
+}
+
+/// Taux de lecture résultant des mods (1.5 pour DT/NC, 0.75 pour HT)
+pub fn clock_rate(map: &rosu_pp::Beatmap, mods: u32) -> f64 {
+    map.attributes().mods(mods).build().clock_rate
+}
+
+/// Extrait les notes d'une beatmap mania, triées par temps puis par colonne
+///
+/// Retourne une erreur si la beatmap n'est pas une beatmap mania native.
+pub fn parse_notes(map: &rosu_pp::Beatmap, mods: u32) -> Result<Vec<ManiaNote>, String> {
+    if map.mode != GameMode::Mania {
+        return Err(format!("Beatmap is not a mania beatmap (mode {:?})", map.mode));
+    }
+
+    let keys = key_count(map);
+    let rate = clock_rate(map, mods);
+
+    let mut notes: Vec<ManiaNote> = map.hit_objects.iter()
+        .map(|object| {
+            let column = ((object.pos.x * keys as f32 / 512.0).floor().max(0.0) as usize).min(keys - 1);
+            let duration = match &object.kind {
+                HitObjectKind::Hold(hold) => hold.duration,
+                _ => 0.0,
+            };
+
+            ManiaNote {
+                time: object.start_time / rate,
+                end_time: (object.start_time + duration) / rate,
+                column,
+            }
+        })
+        .collect();
+
+    notes.sort_by(|a, b| a.time.total_cmp(&b.time).then(a.column.cmp(&b.column)));
+    Ok(notes)
+}
+
+/// Regroupe les notes jouées au même instant (accords)
+pub fn group_chords(notes: &[ManiaNote]) -> Vec<Vec<ManiaNote>> {
+    let mut chords: Vec<Vec<ManiaNote>> = Vec::new();
+
+    for note in notes {
+        match chords.last_mut() {
+            Some(chord) if (chord[0].time - note.time).abs() < 1.0 => chord.push(*note),
+            _ => chords.push(vec![*note]),
+        }
+    }
+
+    chords
+}
@@ -1,3 +1,10 @@
 pub mod hit;
 pub mod osuapi;
-pub mod pp;
\ No newline at end of file
+pub mod pp;
+pub mod mania;
+pub mod quaver;
+pub mod etterna;
+pub mod rating;
+pub mod difficulty;
+pub mod image;
+pub mod mailer;
@@ -1,26 +1,113 @@
 
 use rosu_pp::{GameMods, Performance};
+use rosu_pp::any::{DifficultyAttributes, PerformanceAttributes};
+use serde::Serialize;
+use utoipa::ToSchema;
 use bigdecimal::ToPrimitive;
 use crate::models::score::score::Score;
 use crate::models::map::beatmap::Beatmap;
+use crate::models::map::beatmap_difficulty::BeatmapDifficulty;
+use crate::helpers::rating::{RatingCalculator, RatingResult};
+use async_trait::async_trait;
+use sqlx::PgPool;
+use tokio::sync::OnceCell;
 use tracing::{error, info, warn};
 
-pub async fn calculate_pp_for_score(
-    score: &Score,
-    beatmap: &Beatmap,
-) -> Result<f64, String> {
+/// Calculateur du rating "pp" via rosu-pp
+///
+/// Les attributs de difficulté sont lus depuis le cache `beatmap_difficulty`.
+pub struct PpCalculator {
+    pool: PgPool,
+}
+
+impl PpCalculator {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RatingCalculator for PpCalculator {
+    fn name(&self) -> &'static str {
+        "pp"
+    }
+
+    fn modes(&self) -> &'static [i32] {
+        &[0, 1, 2, 3]
+    }
+
+    fn version(&self) -> i32 {
+        // v2 : max_rating contient le pp d'un SS et fc_rating le pp en full combo
+        // v3 : les composantes du pp sont enregistrées dans attributes
+        // v4 : le pp du score tient compte de son combo réel (sliderbreaks)
+        4
+    }
+
+    async fn calculate(&self, score: &Score, file: &BeatmapFile<'_>) -> Result<RatingResult, String> {
+        let pp = calculate_pp_for_score(&self.pool, score, file).await?;
+        Ok(RatingResult {
+            rating: pp.pp,
+            max_rating: Some(pp.ss_pp),
+            fc_rating: Some(pp.fc_pp),
+            attributes: serde_json::to_value(&pp.breakdown).ok(),
+        })
+    }
+}
+
+/// Télécharge et parse le fichier .osu d'une beatmap
+async fn download_beatmap(beatmap: &Beatmap) -> Result<rosu_pp::Beatmap, String> {
     let filename = beatmap.file_path.split("/").last().unwrap();
     let filename_without_ext = filename.strip_suffix(".osu").unwrap_or(filename);
     let url = format!("https://osu.ppy.sh/osu/{}", filename_without_ext);
     let response = reqwest::get(url).await.map_err(|e| format!("Failed to download beatmap: {}", e))?;
     let beatmap_bytes = response.bytes().await.map_err(|e| format!("Failed to get bytes: {}", e))?;
     
-    let map = rosu_pp::Beatmap::from_bytes(&beatmap_bytes).map_err(|e| format!("Failed to parse beatmap: {}", e))?;
+    rosu_pp::Beatmap::from_bytes(&beatmap_bytes).map_err(|e| format!("Failed to parse beatmap: {}", e))
+}
+
+/// Fichier .osu d'une beatmap, téléchargé et parsé au premier accès puis partagé
+///
+/// Permet à tous les calculs portant sur une même beatmap (pp, Quaver, Etterna, caches)
+/// de n'effectuer qu'un seul téléchargement, et aucun si tout est déjà en cache.
+pub struct BeatmapFile<'a> {
+    beatmap: &'a Beatmap,
+    map: OnceCell<rosu_pp::Beatmap>,
+}
+
+impl<'a> BeatmapFile<'a> {
+    pub fn new(beatmap: &'a Beatmap) -> Self {
+        Self { beatmap, map: OnceCell::new() }
+    }
 
-    // Calculate difficulty attributes
-    let diff_attrs = rosu_pp::Difficulty::new()
-        .mods(score.mods as u32) 
-        .calculate(&map);
+    pub fn beatmap(&self) -> &'a Beatmap {
+        self.beatmap
+    }
+
+    /// Beatmap parsée, téléchargée au premier appel
+    pub async fn get(&self) -> Result<&rosu_pp::Beatmap, String> {
+        self.map.get_or_try_init(|| download_beatmap(self.beatmap)).await
+    }
+}
+
+/// pp d'un score, accompagné du pp en full combo et du pp d'un SS avec les mêmes mods
+#[derive(Debug, Clone)]
+pub struct ScorePp {
+    pub pp: f64,
+    pub fc_pp: f64,
+    pub ss_pp: f64,
+    pub breakdown: PpBreakdown,
+}
+
+pub async fn calculate_pp_for_score(
+    pool: &PgPool,
+    score: &Score,
+    file: &BeatmapFile<'_>,
+) -> Result<ScorePp, String> {
+    // Attributs de difficulté depuis le cache (calculés et enregistrés au besoin)
+    let diff_attrs = BeatmapDifficulty::get_or_calculate(pool, file, score.mods)
+        .await
+        .map_err(|e| format!("Failed to get difficulty attributes: {}", e))?
+        .difficulty_attributes();
 
     let accuracy = score.accuracy.to_f64().unwrap() * 100.0;
     info!("Accuracy : {}", accuracy.clone());
@@ -29,9 +116,17 @@ pub async fn calculate_pp_for_score(
     info!("Game mods : {:?}", game_mods);
     
     let diff_stars = diff_attrs.stars();
+    let fc_pp = simulate_pp(diff_attrs.clone(), &if_fc_input(score, diff_attrs.max_combo())).pp;
+    // Sans jugements ni combo, rosu-pp calcule le pp d'un SS
+    let ss_pp = rosu_pp::Performance::new(diff_attrs.clone())
+        .mods(score.mods as u32)
+        .calculate()
+        .pp();
     let perf_attrs = rosu_pp::Performance::new(diff_attrs)
         .mods(score.mods as u32)
         .accuracy(accuracy)
+        // Sans combo, rosu-pp suppose le meilleur combo possible malgré les misses
+        .combo(score.max_combo as u32)
         .misses(score.statistics.count_miss as u32)
         .n300(score.statistics.count_300 as u32)
         .n100(score.statistics.count_100 as u32)
@@ -42,7 +137,139 @@ pub async fn calculate_pp_for_score(
 
     
     let pp = perf_attrs.pp();
-    info!("Stars : {} - PP : {}", diff_stars, pp);
+    info!("Stars : {} - PP : {} (FC : {}, SS : {})", diff_stars, pp, fc_pp, ss_pp);
     
-    Ok(pp)
-} 
\ No newline at end of file
+    Ok(ScorePp { pp, fc_pp, ss_pp, breakdown: PpBreakdown::from(&perf_attrs) })
+} 
+/// Paramètres d'une simulation de pp ; les valeurs absentes sont déduites par rosu-pp
+#[derive(Debug, Clone, Default)]
+pub struct PpSimulationInput {
+    pub mods: i32,
+    /// Précision en %, ignorée si les nombres de jugements suffisent à la déterminer
+    pub accuracy: Option<f64>,
+    pub n300: Option<u32>,
+    pub n100: Option<u32>,
+    pub n50: Option<u32>,
+    pub n_katu: Option<u32>,
+    pub n_geki: Option<u32>,
+    pub misses: u32,
+    /// Combo maximum atteint ; par défaut le combo maximum de la beatmap moins les misses
+    pub combo: Option<u32>,
+}
+
+/// Répartition du pp entre les différentes composantes (selon le mode)
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct PpBreakdown {
+    pub aim: Option<f64>,
+    pub speed: Option<f64>,
+    pub accuracy: Option<f64>,
+    pub flashlight: Option<f64>,
+    /// Composante de difficulté (taiko, mania)
+    pub difficulty: Option<f64>,
+}
+
+impl From<&PerformanceAttributes> for PpBreakdown {
+    fn from(perf_attrs: &PerformanceAttributes) -> Self {
+        match perf_attrs {
+            PerformanceAttributes::Osu(attrs) => Self {
+                aim: Some(attrs.pp_aim),
+                speed: Some(attrs.pp_speed),
+                accuracy: Some(attrs.pp_acc),
+                flashlight: Some(attrs.pp_flashlight),
+                difficulty: None,
+            },
+            PerformanceAttributes::Taiko(attrs) => Self {
+                accuracy: Some(attrs.pp_acc),
+                difficulty: Some(attrs.pp_difficulty),
+                ..Default::default()
+            },
+            PerformanceAttributes::Mania(attrs) => Self {
+                difficulty: Some(attrs.pp_difficulty),
+                ..Default::default()
+            },
+            PerformanceAttributes::Catch(_) => Self::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PpSimulation {
+    pub pp: f64,
+    pub stars: f64,
+    pub max_combo: u32,
+    pub breakdown: PpBreakdown,
+}
+
+/// Calcule le pp d'un score hypothétique à partir d'attributs de difficulté
+pub fn simulate_pp(diff_attrs: DifficultyAttributes, input: &PpSimulationInput) -> PpSimulation {
+    let stars = diff_attrs.stars();
+    let max_combo = diff_attrs.max_combo();
+
+    let mut performance = Performance::new(diff_attrs)
+        .mods(input.mods as u32)
+        .misses(input.misses);
+    if let Some(accuracy) = input.accuracy {
+        performance = performance.accuracy(accuracy);
+    }
+    if let Some(n300) = input.n300 {
+        performance = performance.n300(n300);
+    }
+    if let Some(n100) = input.n100 {
+        performance = performance.n100(n100);
+    }
+    if let Some(n50) = input.n50 {
+        performance = performance.n50(n50);
+    }
+    if let Some(n_katu) = input.n_katu {
+        performance = performance.n_katu(n_katu);
+    }
+    if let Some(n_geki) = input.n_geki {
+        performance = performance.n_geki(n_geki);
+    }
+    if let Some(combo) = input.combo {
+        performance = performance.combo(combo);
+    }
+
+    let perf_attrs = performance.calculate();
+    let breakdown = PpBreakdown::from(&perf_attrs);
+
+    PpSimulation {
+        pp: perf_attrs.pp(),
+        stars,
+        max_combo,
+        breakdown,
+    }
+}
+
+/// pp qu'aurait rapporté un score sans miss et avec le combo maximum
+///
+/// Les misses sont comptés comme des 300 ; les autres jugements sont conservés.
+pub async fn calculate_if_fc_pp(
+    pool: &PgPool,
+    score: &Score,
+    beatmap: &Beatmap,
+) -> Result<f64, String> {
+    let diff_attrs = BeatmapDifficulty::get_or_calculate(pool, &BeatmapFile::new(beatmap), score.mods)
+        .await
+        .map_err(|e| format!("Failed to get difficulty attributes: {}", e))?
+        .difficulty_attributes();
+
+    let input = if_fc_input(score, diff_attrs.max_combo());
+    Ok(simulate_pp(diff_attrs, &input).pp)
+}
+
+/// Jugements d'un score dont les misses sont remplacés par des 300, avec le combo maximum
+fn if_fc_input(score: &Score, max_combo: u32) -> PpSimulationInput {
+    let statistics = &score.statistics;
+    PpSimulationInput {
+        mods: score.mods,
+        n300: Some((statistics.count_300 + statistics.count_miss) as u32),
+        n100: Some(statistics.count_100 as u32),
+        n50: Some(statistics.count_50 as u32),
+        n_katu: Some(statistics.count_katu as u32),
+        n_geki: Some(statistics.count_geki as u32),
+        misses: 0,
+        combo: Some(max_combo),
+        ..Default::default()
+    }
+}
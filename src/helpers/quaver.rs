@@ -0,0 +1,179 @@
+use crate::helpers::mania::{group_chords, key_count, parse_notes, ManiaNote};
+use crate::helpers::pp::BeatmapFile;
+use crate::helpers::rating::{RatingCalculator, RatingResult};
+use crate::models::score::score::{Score, ScoreStatistics};
+use async_trait::async_trait;
+use tracing::info;
+
+/// Nom du rating_type correspondant
+pub const QUAVER_RATING_TYPE: &str = "quaver";
+
+/// Durée d'une section de strain en ms
+const SECTION_LEN: f64 = 500.0;
+/// Écart minimal entre deux actions d'une même main, pour éviter les valeurs infinies
+const MIN_DELTA: f64 = 25.0;
+/// Bonus par note supplémentaire dans un accord
+const CHORD_BONUS: f64 = 0.08;
+/// Bonus lorsqu'une action réutilise une colonne de l'action précédente (jack)
+const JACK_BONUS: f64 = 1.25;
+/// Bonus lorsqu'une action contient une note longue
+const LONG_NOTE_BONUS: f64 = 1.1;
+/// Facteur de calibration pour ramener les valeurs à l'échelle des ratings Quaver
+const DIFFICULTY_SCALE: f64 = 3.0;
+
+/// Résultat d'un calcul de rating Quaver pour un score
+#[derive(Debug, Clone, Copy)]
+pub struct QuaverRating {
+    pub difficulty: f64,
+    pub accuracy: f64,
+    pub rating: f64,
+    pub max_rating: f64,
+}
+
+/// Précision au sens de Quaver (en %) à partir des jugements osu!mania
+///
+/// MAX → Marvelous, 300 → Perfect, 200 → Great, 100 → Good, 50 → Okay.
+pub fn quaver_accuracy(statistics: &ScoreStatistics) -> f64 {
+    let judgements = [
+        (statistics.count_geki, 100.0),
+        (statistics.count_300, 98.25),
+        (statistics.count_katu, 65.0),
+        (statistics.count_100, 25.0),
+        (statistics.count_50, -100.0),
+        (statistics.count_miss, -50.0),
+    ];
+
+    let total: i32 = judgements.iter().map(|(count, _)| count).sum();
+    if total <= 0 {
+        return 0.0;
+    }
+
+    let points: f64 = judgements.iter().map(|(count, weight)| *count as f64 * weight).sum();
+    (points / (total as f64 * 100.0) * 100.0).max(0.0)
+}
+
+/// Rating de performance Quaver pour une difficulté et une précision (en %)
+pub fn performance_rating(difficulty: f64, accuracy: f64) -> f64 {
+    difficulty * (accuracy / 98.0).powi(6)
+}
+
+/// Difficulté d'une beatmap mania façon Quaver
+///
+/// Chaque main est analysée séparément : la strain d'une action dépend du temps écoulé
+/// depuis l'action précédente de la même main, avec des bonus pour les accords,
+/// les jacks et les notes longues. La difficulté finale combine la moyenne des sections
+/// et la moyenne des sections les plus difficiles.
+pub fn calculate_difficulty(notes: &[ManiaNote], keys: usize) -> f64 {
+    let Some(last) = notes.last() else { return 0.0 };
+    let section_count = (last.time / SECTION_LEN).floor() as usize + 1;
+    let mut sections = vec![0.0_f64; section_count];
+
+    for hand in [Hand::Left, Hand::Right] {
+        let hand_notes: Vec<ManiaNote> = notes.iter()
+            .filter(|note| hand.contains(note.column, keys))
+            .copied()
+            .collect();
+
+        let mut section_sums = vec![(0.0_f64, 0_u32); section_count];
+        let chords = group_chords(&hand_notes);
+
+        for pair in chords.windows(2) {
+            let (previous, chord) = (&pair[0], &pair[1]);
+            let delta = (chord[0].time - previous[0].time).max(MIN_DELTA);
+
+            let mut strain = 1000.0 / delta;
+            strain *= 1.0 + CHORD_BONUS * (chord.len() - 1) as f64;
+            if chord.iter().any(|note| previous.iter().any(|p| p.column == note.column)) {
+                strain *= JACK_BONUS;
+            }
+            if chord.iter().any(|note| note.end_time > note.time) {
+                strain *= LONG_NOTE_BONUS;
+            }
+
+            let index = ((chord[0].time / SECTION_LEN).floor().max(0.0) as usize).min(section_count - 1);
+            section_sums[index].0 += strain;
+            section_sums[index].1 += 1;
+        }
+
+        for (section, (sum, count)) in sections.iter_mut().zip(section_sums) {
+            if count > 0 {
+                *section = section.max(sum / count as f64);
+            }
+        }
+    }
+
+    let mut active: Vec<f64> = sections.into_iter().filter(|strain| *strain > 0.0).collect();
+    if active.is_empty() {
+        return 0.0;
+    }
+    active.sort_by(|a, b| b.total_cmp(a));
+
+    let mean = active.iter().sum::<f64>() / active.len() as f64;
+    let top_len = (active.len() / 10).max(1);
+    let top_mean = active[..top_len].iter().sum::<f64>() / top_len as f64;
+
+    (mean * 0.6 + top_mean * 0.4) * DIFFICULTY_SCALE
+}
+
+/// Calcule le rating Quaver d'un score osu!mania
+pub async fn calculate_quaver_for_score(
+    score: &Score,
+    file: &BeatmapFile<'_>,
+) -> Result<QuaverRating, String> {
+    let map = file.get().await?;
+    let notes = parse_notes(map, score.mods as u32)?;
+
+    let difficulty = calculate_difficulty(&notes, key_count(map));
+    let accuracy = quaver_accuracy(&score.statistics);
+    let rating = performance_rating(difficulty, accuracy);
+    let max_rating = performance_rating(difficulty, 100.0);
+
+    info!("Quaver - Difficulty : {} - Accuracy : {} - Rating : {}", difficulty, accuracy, rating);
+
+    Ok(QuaverRating {
+        difficulty,
+        accuracy,
+        rating,
+        max_rating,
+    })
+}
+
+/// Calculateur du rating Quaver, pour les scores osu!mania
+pub struct QuaverCalculator;
+
+#[async_trait]
+impl RatingCalculator for QuaverCalculator {
+    fn name(&self) -> &'static str {
+        QUAVER_RATING_TYPE
+    }
+
+    fn modes(&self) -> &'static [i32] {
+        &[3]
+    }
+
+    fn version(&self) -> i32 {
+        1
+    }
+
+    async fn calculate(&self, score: &Score, file: &BeatmapFile<'_>) -> Result<RatingResult, String> {
+        let quaver = calculate_quaver_for_score(score, file).await?;
+        Ok(RatingResult { rating: quaver.rating, max_rating: Some(quaver.max_rating), fc_rating: None, attributes: None })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    /// La colonne centrale des modes impairs est jouée par les deux mains
+    fn contains(self, column: usize, keys: usize) -> bool {
+        let doubled = column * 2 + 1;
+        match self {
+            Hand::Left => doubled <= keys,
+            Hand::Right => doubled >= keys,
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use sqlx::PgPool;
+use sqlx::types::JsonValue;
+use tracing::{error, info, warn};
+use crate::helpers::etterna::EtternaCalculator;
+use crate::helpers::pp::{BeatmapFile, PpCalculator};
+use crate::helpers::quaver::QuaverCalculator;
+use crate::models::map::beatmap::Beatmap;
+use crate::models::score::score::Score;
+use crate::models::score::score_rating::{CreateScoreRating, RatingType, ScoreRating};
+
+/// Résultat d'un calcul de rating pour un score
+#[derive(Debug, Clone)]
+pub struct RatingResult {
+    pub rating: f64,
+    /// Rating d'un SS avec les mêmes mods
+    pub max_rating: Option<f64>,
+    /// Rating du même score en full combo, pour les systèmes sensibles au combo
+    pub fc_rating: Option<f64>,
+    /// Détail du rating propre au système (ex. composantes du pp)
+    pub attributes: Option<JsonValue>,
+}
+
+/// Un système de rating (pp, Quaver, Etterna...)
+///
+/// Ajouter un nouveau système revient à implémenter ce trait, à l'ajouter dans
+/// [`calculators`] et à créer la ligne `rating_type` correspondante.
+#[async_trait]
+pub trait RatingCalculator: Send + Sync {
+    /// Nom du rating_type correspondant
+    fn name(&self) -> &'static str;
+
+    /// Modes de jeu supportés (0: osu, 1: taiko, 2: catch, 3: mania)
+    fn modes(&self) -> &'static [i32];
+
+    /// Version de l'algorithme, à incrémenter à chaque changement de formule
+    fn version(&self) -> i32;
+
+    /// Calcule le rating d'un score ; le fichier .osu est partagé entre les calculateurs
+    async fn calculate(&self, score: &Score, file: &BeatmapFile<'_>) -> Result<RatingResult, String>;
+
+    /// Appelé après l'enregistrement d'un rating, pour mettre à jour des données dérivées
+    async fn after_store(&self, _score: &Score) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn supports(&self, mode: i32) -> bool {
+        self.modes().contains(&mode)
+    }
+}
+
+/// Toutes les implémentations connues
+pub fn calculators(pool: &PgPool) -> Vec<Box<dyn RatingCalculator>> {
+    vec![
+        Box::new(PpCalculator::new(pool.clone())),
+        Box::new(QuaverCalculator),
+        Box::new(EtternaCalculator::new(pool.clone())),
+    ]
+}
+
+/// Calculateurs associés aux rating_type actifs en base
+#[derive(Default)]
+pub struct RatingRegistry {
+    entries: Vec<(RatingType, Box<dyn RatingCalculator>)>,
+}
+
+impl RatingRegistry {
+    /// Construit le registre à partir des rating_type actifs
+    ///
+    /// Les rating_type actifs sans implémentation sont ignorés avec un avertissement.
+    pub async fn load(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let mut available = calculators(pool);
+        let mut entries = Vec::new();
+
+        for rating_type in RatingType::get_all_active(pool).await? {
+            match available.iter().position(|c| c.name() == rating_type.name) {
+                Some(index) => entries.push((rating_type, available.swap_remove(index))),
+                None => warn!("No calculator registered for active rating type '{}'", rating_type.name),
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Restreint le registre à un seul rating_type
+    pub fn only(mut self, name: &str) -> Self {
+        self.entries.retain(|(rating_type, _)| rating_type.name == name);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Calculateurs applicables à un mode de jeu
+    pub fn for_mode(&self, mode: i32) -> impl Iterator<Item = (&RatingType, &dyn RatingCalculator)> {
+        self.entries.iter()
+            .filter(move |(_, calculator)| calculator.supports(mode))
+            .map(|(rating_type, calculator)| (rating_type, calculator.as_ref()))
+    }
+
+    /// Calcule et enregistre tous les ratings applicables à un score
+    ///
+    /// Un rating existant du même type est remplacé. Retourne le nombre d'échecs.
+    pub async fn compute_and_store(&self, pool: &PgPool, score: &Score, beatmap: &Beatmap) -> usize {
+        self.recompute(pool, score, beatmap, false).await
+    }
+
+    /// Comme [`Self::compute_and_store`], mais peut se limiter aux ratings absents
+    /// ou produits par une version antérieure du calculateur
+    pub async fn recompute(&self, pool: &PgPool, score: &Score, beatmap: &Beatmap, only_outdated: bool) -> usize {
+        let existing = if only_outdated {
+            match ScoreRating::get_by_score(pool, score.id).await {
+                Ok(existing) => existing,
+                Err(e) => {
+                    error!("Failed to get existing ratings for score {}: {}", score.id, e);
+                    return 1;
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        let mut failed = 0;
+        let file = BeatmapFile::new(beatmap);
+
+        for (rating_type, calculator) in self.for_mode(beatmap.mode) {
+            let up_to_date = existing.iter().any(|rating| {
+                rating.rating_type_id == rating_type.id && rating.calculator_version >= calculator.version()
+            });
+            if up_to_date {
+                continue;
+            }
+
+            let result = match calculator.calculate(score, &file).await {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to calculate {} (v{}) for score {}: {}", rating_type.name, calculator.version(), score.id, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            let create_rating = CreateScoreRating {
+                score_id: score.id,
+                rating_type_id: rating_type.id,
+                rating_value: BigDecimal::try_from(result.rating).unwrap_or_default(),
+                max_rating: result.max_rating.map(|max| BigDecimal::try_from(max).unwrap_or_default()),
+                fc_rating: result.fc_rating.map(|fc| BigDecimal::try_from(fc).unwrap_or_default()),
+                attributes: result.attributes,
+                calculator_version: calculator.version(),
+            };
+
+            if let Err(e) = ScoreRating::replace(pool, create_rating).await {
+                error!("Failed to store {} rating for score {}: {}", rating_type.name, score.id, e);
+                failed += 1;
+                continue;
+            }
+            info!("Stored {} rating {} for score {}", rating_type.name, result.rating, score.id);
+
+            if let Err(e) = calculator.after_store(score).await {
+                error!("Post-processing of {} rating for score {} failed: {}", rating_type.name, score.id, e);
+            }
+        }
+
+        failed
+    }
+}
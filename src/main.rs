@@ -20,14 +20,18 @@ mod middleware;
 pub mod helpers;
 pub mod auth;
 
-use axum::Router;
+use axum::{Extension, Router};
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
 use tracing::{info, error};
 use fixtures::run_fixtures;
 use middleware::logging::setup_middleware;
 use models::map::beatmap_queue::BeatmapQueue;
+use models::user::rank_history::UserRankHistory;
+use models::score::rating_job::RatingRecalcJob;
 use helpers::osuapi::OsuAPI;
+use helpers::mailer::MailService;
+use auth::JwtKeys;
 /// Point d'entrée principal de l'application.
 ///
 /// Cette fonction :
@@ -74,16 +78,31 @@ async fn main() {
         .expect("Failed to initialize beatmap queue");
     info!("Beatmap queue initialized");
 
+    UserRankHistory::init(db.get_pool().clone(), config.history.clone().unwrap_or_default())
+        .await
+        .expect("Failed to initialize rank history snapshots");
+    info!("Rank history snapshots initialized");
+
+    RatingRecalcJob::init(db.get_pool().clone())
+        .await
+        .expect("Failed to initialize rating recalculation jobs");
+    info!("Rating recalculation jobs initialized");
+
+    let mail = MailService::from_config(&config.mailer.clone().unwrap_or_default())
+        .expect("Failed to configure mailer");
+
     // Build our application with a route
     let mut app = Router::new()
-        .merge(routes::create_router(db));
+        .merge(routes::create_router(db))
+        .layer(Extension(mail))
+        .layer(Extension(JwtKeys::from_config(&config.auth)));
 
     // CORS for localhost development - Comment/Uncomment as needed
     // app = app.layer(CorsLayer::permissive()); // Permissive CORS for all origins
      app = app.layer(
          CorsLayer::new()
             .allow_origin("*".parse::<axum::http::HeaderValue>().unwrap())
-            .allow_methods([axum::http::Method::GET, axum::http::Method::POST, axum::http::Method::PUT, axum::http::Method::DELETE])
+            .allow_methods([axum::http::Method::GET, axum::http::Method::POST, axum::http::Method::PUT, axum::http::Method::PATCH, axum::http::Method::DELETE])
             .allow_headers([axum::http::header::CONTENT_TYPE, axum::http::header::AUTHORIZATION])
     );
 
@@ -98,7 +117,7 @@ async fn main() {
     info!("listening on {}", addr);
     axum::serve(
         tokio::net::TcpListener::bind(addr).await.unwrap(),
-        app.into_make_service(),
+        app.into_make_service_with_connect_info::<SocketAddr>(),
     )
     .await
     .unwrap();
@@ -6,6 +6,7 @@ use axum::{
 };
 use sqlx::PgPool;
 use crate::auth;
+use crate::models::user::session::{CurrentSession, UserSession};
 use crate::models::user::user::User;
 
 pub async fn auth_middleware(
@@ -25,15 +26,60 @@ pub async fn auth_middleware(
         .strip_prefix("Bearer ")
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    let token_data = auth::decode_jwt(token)
+    let keys = req.extensions()
+        .get::<auth::JwtKeys>()
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let token_data = auth::decode_jwt(keys, token)
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    // Récupérer l'utilisateur depuis la base de données
-    let user = match User::get_by_id(&pool, token_data.claims.sub.parse().unwrap()).await {
+    // Récupérer l'utilisateur de la session, si elle est encore active
+    let user_id = token_data.claims.sub.parse().map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user = match UserSession::get_active_user(&pool, token_data.claims.sid, user_id).await {
         Ok(Some(user)) => user,
         _ => return Err(StatusCode::UNAUTHORIZED),
     };
 
+    // Refuser les JWT émis avant une réinitialisation du mot de passe
+    if !user.accepts_token_issued_at(token_data.claims.iat) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
     req.extensions_mut().insert(user);
+    req.extensions_mut().insert(CurrentSession(token_data.claims.sid));
+    Ok(next.run(req).await)
+} 
+/// Restreint l'accès aux utilisateurs ayant le rôle "admin"
+///
+/// Doit être placé derrière `auth_middleware`, qui insère l'utilisateur dans la requête.
+pub async fn admin_middleware(
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let user = req.extensions()
+        .get::<User>()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !user.has_role("admin") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Restreint l'accès aux utilisateurs dont l'adresse email est vérifiée
+///
+/// Doit être placé derrière `auth_middleware`, qui insère l'utilisateur dans la requête.
+pub async fn verified_middleware(
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let user = req.extensions()
+        .get::<User>()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !user.is_verified {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     Ok(next.run(req).await)
-} 
\ No newline at end of file
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, JsonValue};
+use sqlx::PgConnection;
+use utoipa::ToSchema;
+
+/// Canal PostgreSQL sur lequel chaque nouvel événement est notifié (LISTEN events)
+pub const EVENTS_CHANNEL: &str = "events";
+/// Clé du verrou consultatif sérialisant l'écriture des événements
+const EVENTS_LOCK_KEY: i64 = 0x6576_656e_7473;
+
+/// Événement métier destiné aux notifications et webhooks
+///
+/// Les événements sont écrits dans la transaction qui les produit : ils ne sont visibles
+/// (et notifiés) que si cette transaction est validée. Les consommateurs peuvent écouter
+/// le canal [`EVENTS_CHANNEL`] ou interroger la table à partir du dernier id traité :
+/// les ids sont attribués sous un verrou tenu jusqu'à la validation, ils suivent donc
+/// l'ordre de validation et aucun événement ne peut apparaître derrière un id déjà lu.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Event {
+    pub id: i64,
+    pub event_type: String,
+    pub payload: JsonValue,
+    pub created_at: NaiveDateTime,
+}
+
+impl Event {
+    /// Une beatmap a un nouveau détenteur de la première place
+    pub const FIRST_PLACE_TAKEN: &'static str = "first_place_taken";
+
+    pub async fn emit(conn: &mut PgConnection, event_type: &str, payload: JsonValue) -> Result<Self, sqlx::Error> {
+        sqlx::query!(
+            r#"
+            SELECT pg_advisory_xact_lock($1)
+            "#,
+            EVENTS_LOCK_KEY
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO event (event_type, payload)
+            VALUES ($1, $2)
+            RETURNING *
+            "#,
+            event_type,
+            payload
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            SELECT pg_notify($1, json_build_object('id', $2::bigint, 'event_type', $3::varchar)::text)
+            "#,
+            EVENTS_CHANNEL,
+            record.id,
+            record.event_type
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Événements postérieurs à `after_id`, du plus ancien au plus récent
+    pub async fn get_after(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        after_id: i64,
+        event_type: Option<&str>,
+        limit: i64
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT * FROM event
+            WHERE id > $1 AND ($2::varchar IS NULL OR event_type = $2)
+            ORDER BY id
+            LIMIT $3
+            "#,
+            after_id,
+            event_type,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+}
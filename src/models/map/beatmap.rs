@@ -6,6 +6,7 @@ use sqlx::PgPool;
 use anyhow::Result;
 use crate::helpers::osuapi::{OsuAPI, BeatmapResponse};
 use crate::models::map::beatmap_queue::BeatmapQueue;
+use crate::helpers::difficulty::{difficulty_mods, modded_attributes, DIFFICULTY_CALCULATOR_VERSION};
 use tracing::info;
 
 #[derive(Deserialize)]
@@ -13,6 +14,7 @@ pub struct RandomBeatmapQuery {
     pub mode: i32,
     pub status: String,
     pub limit_difficulty: Option<BigDecimal>,
+    pub mods: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -20,6 +22,8 @@ pub struct RandomBeatmapQuerySchema {
     pub mode: i32,
     pub status: String,
     pub limit_difficulty: Option<f64>,
+    /// Mods pour la limite de difficulté : utilise les étoiles en cache pour ces mods si elles existent
+    pub mods: Option<i32>,
 }
 
 impl RandomBeatmapQuery {
@@ -28,6 +32,7 @@ impl RandomBeatmapQuery {
             mode: self.mode,
             status: self.status.clone(),
             limit_difficulty: self.limit_difficulty.as_ref().map(|d| d.to_string().parse::<f64>().unwrap_or(0.0)),
+            mods: self.mods,
         }
     }
 }
@@ -80,6 +85,8 @@ pub struct BeatmapSchema {
     pub file_path: String,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    /// Mods appliqués aux attributs, étoiles, BPM et durées (0 : valeurs brutes)
+    pub mods: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -134,21 +141,57 @@ impl Beatmap {
             file_path: self.file_path.clone(),
             created_at: self.created_at,
             updated_at: self.updated_at,
+            mods: 0,
         }
     }
 
+    /// Schéma avec les valeurs effectives sous des mods : AR/OD/CS/HP, BPM et durées
+    /// (selon la vitesse de DT/HT), et étoiles si elles sont fournies
+    pub fn to_schema_with_mods(&self, mods: i32, stars: Option<f64>) -> BeatmapSchema {
+        let mut schema = self.to_schema();
+        let mods = difficulty_mods(mods);
+        if mods == 0 {
+            return schema;
+        }
+
+        let modded = modded_attributes(self.mode, schema.ar, schema.od, schema.cs, schema.hp, mods);
+        let scale_time = |time: i32| (time as f64 / modded.clock_rate).round() as i32;
+        // rosu-pp travaille en f32 : on arrondit pour éviter des valeurs comme 5.199999809
+        let round = |value: f64| (value * 100.0).round() / 100.0;
+
+        schema.ar = round(modded.ar);
+        schema.od = round(modded.od);
+        schema.cs = round(modded.cs);
+        schema.hp = round(modded.hp);
+        schema.bpm *= modded.clock_rate;
+        schema.drain_time = scale_time(self.drain_time);
+        schema.total_time = scale_time(self.total_time);
+        schema.hit_length = scale_time(self.hit_length);
+        if let Some(stars) = stars {
+            schema.difficulty_rating = stars;
+        }
+        schema.mods = mods;
+        schema
+    }
+
     // Get a random beatmap from the database
-    pub async fn get_random_beatmap(pool: &sqlx::Pool<sqlx::Postgres>, mode: i32, status: &str, limit_difficulty: Option<f64>) -> Result<Option<Self>, sqlx::Error> {
+    pub async fn get_random_beatmap(pool: &sqlx::Pool<sqlx::Postgres>, mode: i32, status: &str, limit_difficulty: Option<f64>, mods: Option<i32>) -> Result<Option<Self>, sqlx::Error> {
+        // Avec des mods, la limite porte sur les étoiles en cache pour ces mods (à défaut, celles sans mods)
         let record = sqlx::query_as!(
             Self,
             r#"
-            SELECT * FROM beatmap WHERE mode = $1 AND status = $2 
-            AND ($3::float8 IS NULL OR difficulty_rating <= $3::float8)
+            SELECT b.* FROM beatmap b
+            LEFT JOIN beatmap_difficulty bd ON $4::integer IS NOT NULL
+                AND bd.beatmap_id = b.id AND bd.mods = $4 AND bd.calculator_version = $5
+            WHERE b.mode = $1 AND b.status = $2 
+            AND ($3::float8 IS NULL OR COALESCE(bd.stars, b.difficulty_rating) <= $3::float8)
             ORDER BY RANDOM() LIMIT 1
             "#,
             mode,
             status,
-            limit_difficulty
+            limit_difficulty,
+            mods.map(difficulty_mods),
+            DIFFICULTY_CALCULATOR_VERSION
         )
         .fetch_optional(pool)
         .await?;
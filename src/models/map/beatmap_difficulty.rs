@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, BigDecimal, Json};
+use utoipa::ToSchema;
+use rosu_pp::any::DifficultyAttributes;
+use crate::helpers::difficulty::{compute_difficulty, difficulty_mods, CachedAttributes, DIFFICULTY_CALCULATOR_VERSION};
+use crate::helpers::pp::BeatmapFile;
+
+/// Attributs de difficulté d'une beatmap pour une combinaison de mods, mis en cache
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BeatmapDifficulty {
+    pub id: i32,
+    pub beatmap_id: i32,
+    pub mods: i32,
+    pub calculator_version: i32,
+    pub stars: BigDecimal,
+    pub aim: Option<BigDecimal>,
+    pub speed: Option<BigDecimal>,
+    pub flashlight: Option<BigDecimal>,
+    pub max_combo: i32,
+    pub ar: BigDecimal,
+    pub od: BigDecimal,
+    pub hp: BigDecimal,
+    pub cs: BigDecimal,
+    pub attributes: Json<CachedAttributes>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BeatmapDifficultySchema {
+    pub beatmap_id: i32,
+    /// Mods influant sur la difficulté (NC est ramené à DT)
+    pub mods: i32,
+    pub stars: f64,
+    pub aim: Option<f64>,
+    pub speed: Option<f64>,
+    pub flashlight: Option<f64>,
+    pub max_combo: i32,
+    pub ar: f64,
+    pub od: f64,
+    pub hp: f64,
+    pub cs: f64,
+}
+
+fn to_f64(value: &BigDecimal) -> f64 {
+    value.to_string().parse::<f64>().unwrap_or(0.0)
+}
+
+impl BeatmapDifficulty {
+    pub fn to_schema(&self) -> BeatmapDifficultySchema {
+        BeatmapDifficultySchema {
+            beatmap_id: self.beatmap_id,
+            mods: self.mods,
+            stars: to_f64(&self.stars),
+            aim: self.aim.as_ref().map(to_f64),
+            speed: self.speed.as_ref().map(to_f64),
+            flashlight: self.flashlight.as_ref().map(to_f64),
+            max_combo: self.max_combo,
+            ar: to_f64(&self.ar),
+            od: to_f64(&self.od),
+            hp: to_f64(&self.hp),
+            cs: to_f64(&self.cs),
+        }
+    }
+
+    /// Attributs rosu-pp reconstruits, utilisables directement par `rosu_pp::Performance`
+    pub fn difficulty_attributes(&self) -> DifficultyAttributes {
+        DifficultyAttributes::from(&self.attributes.0)
+    }
+
+    pub async fn get(pool: &sqlx::Pool<sqlx::Postgres>, beatmap_id: i32, mods: i32) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT id, beatmap_id, mods, calculator_version, stars, aim, speed, flashlight, max_combo,
+                   ar, od, hp, cs, attributes as "attributes: Json<CachedAttributes>", created_at
+            FROM beatmap_difficulty
+            WHERE beatmap_id = $1 AND mods = $2 AND calculator_version = $3
+            "#,
+            beatmap_id,
+            difficulty_mods(mods),
+            DIFFICULTY_CALCULATOR_VERSION
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Calcule et enregistre les attributs d'une beatmap déjà téléchargée
+    pub async fn calculate_and_store(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        beatmap_id: i32,
+        map: &rosu_pp::Beatmap,
+        mods: i32,
+    ) -> Result<Self, sqlx::Error> {
+        let computed = compute_difficulty(map, mods);
+        let decimal = |value: f64| BigDecimal::try_from(value).unwrap_or_default();
+        let (aim, speed, flashlight) = match &computed.attributes {
+            DifficultyAttributes::Osu(attrs) => (
+                Some(decimal(attrs.aim)),
+                Some(decimal(attrs.speed)),
+                Some(decimal(attrs.flashlight)),
+            ),
+            _ => (None, None, None),
+        };
+
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO beatmap_difficulty
+                (beatmap_id, mods, calculator_version, stars, aim, speed, flashlight, max_combo, ar, od, hp, cs, attributes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (beatmap_id, mods, calculator_version) DO UPDATE
+            SET stars = EXCLUDED.stars,
+                aim = EXCLUDED.aim,
+                speed = EXCLUDED.speed,
+                flashlight = EXCLUDED.flashlight,
+                max_combo = EXCLUDED.max_combo,
+                ar = EXCLUDED.ar,
+                od = EXCLUDED.od,
+                hp = EXCLUDED.hp,
+                cs = EXCLUDED.cs,
+                attributes = EXCLUDED.attributes
+            RETURNING id, beatmap_id, mods, calculator_version, stars, aim, speed, flashlight, max_combo,
+                      ar, od, hp, cs, attributes as "attributes: Json<CachedAttributes>", created_at
+            "#,
+            beatmap_id,
+            difficulty_mods(mods),
+            DIFFICULTY_CALCULATOR_VERSION,
+            decimal(computed.attributes.stars()),
+            aim,
+            speed,
+            flashlight,
+            computed.attributes.max_combo() as i32,
+            decimal(computed.ar),
+            decimal(computed.od),
+            decimal(computed.hp),
+            decimal(computed.cs),
+            Json(CachedAttributes::from(&computed.attributes)) as _
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Récupère les attributs en cache, ou télécharge la beatmap pour les calculer
+    pub async fn get_or_calculate(pool: &sqlx::Pool<sqlx::Postgres>, file: &BeatmapFile<'_>, mods: i32) -> anyhow::Result<Self> {
+        let beatmap_id = file.beatmap().id;
+        if let Some(cached) = Self::get(pool, beatmap_id, mods).await? {
+            return Ok(cached);
+        }
+
+        let map = file.get().await.map_err(anyhow::Error::msg)?;
+        Ok(Self::calculate_and_store(pool, beatmap_id, map, mods).await?)
+    }
+}
@@ -10,8 +10,7 @@ use crate::models::score::score::{Score, CreateScore};
 use sqlx::types::BigDecimal;
 use tracing::{error, warn};
 use flume::{Sender, Receiver};
-use crate::helpers::pp::calculate_pp_for_score;
-use crate::models::score::score_rating::{ScoreRating, RatingType, CreateScoreRating};
+use crate::helpers::rating::RatingRegistry;
 // Structure pour les requêtes de beatmap
 #[derive(Debug, Clone)]
 pub struct BeatmapRequest {
@@ -129,6 +128,10 @@ impl BeatmapQueue {
     /// Traite les scores pour une beatmap
     async fn process_scores(scores: &[CreateScore], beatmap_id: i32, pool: &PgPool) {
         let mut error_count = 0;
+        let registry = RatingRegistry::load(pool).await.unwrap_or_else(|e| {
+            error!("Échec du chargement des types de rating, aucun rating ne sera calculé: {}", e);
+            RatingRegistry::default()
+        });
         
         for score in scores {
             let score = Score::create(pool, score.clone()).await;
@@ -138,16 +141,14 @@ impl BeatmapQueue {
             }
             else{
                 let score = score.unwrap();
-                let beatmap = Beatmap::get_by_id(pool, beatmap_id).await.unwrap().unwrap();
-                let pp = calculate_pp_for_score(&score, &beatmap).await.unwrap();
-                let rating_type = RatingType::get_by_name(pool, "pp").await.unwrap().unwrap();
-                let sr  = CreateScoreRating{
-                    score_id: score.id,
-                    rating_type_id: rating_type.id,
-                    rating_value: BigDecimal::try_from(pp).unwrap_or_default(),
-                    max_rating: Some(BigDecimal::try_from(pp).unwrap_or_default()),
-                    };
-                    let sr = ScoreRating::create(pool, sr).await;
+                let beatmap = match Beatmap::get_by_id(pool, beatmap_id).await {
+                    Ok(Some(beatmap)) => beatmap,
+                    _ => {
+                        error!("Beatmap {} introuvable pour le score {}", beatmap_id, score.id);
+                        continue;
+                    }
+                };
+                registry.compute_and_store(pool, &score, &beatmap).await;
             }
         }
     }
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, Json};
+use crate::helpers::difficulty::{compute_strains, difficulty_mods, StrainGraph, DIFFICULTY_CALCULATOR_VERSION};
+use crate::helpers::pp::BeatmapFile;
+
+/// Courbes de strain d'une beatmap pour une combinaison de mods, mises en cache
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BeatmapStrains {
+    pub id: i32,
+    pub beatmap_id: i32,
+    pub mods: i32,
+    pub calculator_version: i32,
+    pub graph: Json<StrainGraph>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl BeatmapStrains {
+    pub async fn get(pool: &sqlx::Pool<sqlx::Postgres>, beatmap_id: i32, mods: i32) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT id, beatmap_id, mods, calculator_version, graph as "graph: Json<StrainGraph>", created_at
+            FROM beatmap_strains
+            WHERE beatmap_id = $1 AND mods = $2 AND calculator_version = $3
+            "#,
+            beatmap_id,
+            difficulty_mods(mods),
+            DIFFICULTY_CALCULATOR_VERSION
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Calcule et enregistre les courbes d'une beatmap déjà téléchargée
+    pub async fn calculate_and_store(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        beatmap_id: i32,
+        map: &rosu_pp::Beatmap,
+        mods: i32,
+    ) -> Result<Self, sqlx::Error> {
+        let graph = compute_strains(map, mods);
+
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO beatmap_strains (beatmap_id, mods, calculator_version, graph)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (beatmap_id, mods, calculator_version) DO UPDATE
+            SET graph = EXCLUDED.graph
+            RETURNING id, beatmap_id, mods, calculator_version, graph as "graph: Json<StrainGraph>", created_at
+            "#,
+            beatmap_id,
+            difficulty_mods(mods),
+            DIFFICULTY_CALCULATOR_VERSION,
+            Json(graph) as _
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Récupère les courbes en cache, ou télécharge la beatmap pour les calculer
+    pub async fn get_or_calculate(pool: &sqlx::Pool<sqlx::Postgres>, file: &BeatmapFile<'_>, mods: i32) -> anyhow::Result<Self> {
+        let beatmap_id = file.beatmap().id;
+        if let Some(cached) = Self::get(pool, beatmap_id, mods).await? {
+            return Ok(cached);
+        }
+
+        let map = file.get().await.map_err(anyhow::Error::msg)?;
+        Ok(Self::calculate_and_store(pool, beatmap_id, map, mods).await?)
+    }
+}
@@ -1,3 +1,5 @@
 pub mod beatmap;
 pub mod beatmapset; 
-pub mod beatmap_queue; 
\ No newline at end of file
+pub mod beatmap_queue;
+pub mod beatmap_difficulty;
+pub mod beatmap_strains;
\ No newline at end of file
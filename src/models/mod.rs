@@ -8,4 +8,5 @@ pub mod help;
 pub mod map;
 pub mod score;
 pub mod user;
-pub mod ranked;
\ No newline at end of file
+pub mod ranked;
+pub mod event;
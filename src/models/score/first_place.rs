@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::PgConnection;
+use utoipa::ToSchema;
+use crate::models::event::Event;
+use crate::models::score::user_beatmap_best::ALL_MODS;
+
+/// Critère du classement dont la première place est suivie
+pub const FIRST_PLACE_CRITERIA: &str = "score";
+
+/// Première place actuelle d'une beatmap (classement au score, tous mods confondus)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BeatmapFirstPlace {
+    pub beatmap_id: i32,
+    pub user_id: i32,
+    pub score_id: Option<i32>,
+    pub since: NaiveDateTime,
+}
+
+/// Changement de première place
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FirstPlaceChange {
+    pub id: i32,
+    pub beatmap_id: i32,
+    /// Nouveau détenteur
+    pub user_id: i32,
+    pub username: String,
+    pub score_id: i32,
+    /// Ancien détenteur, absent s'il s'agit du premier score de la beatmap
+    pub previous_user_id: Option<i32>,
+    pub previous_username: Option<String>,
+    pub previous_score_id: Option<i32>,
+    /// `true` si la place a été prise par un meilleur score, `false` si l'ancien score a été supprimé ou masqué
+    pub sniped: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl BeatmapFirstPlace {
+    /// Met à jour la première place d'une beatmap d'après `user_beatmap_best`
+    ///
+    /// Appelé dans la transaction qui modifie les meilleurs scores : un changement de
+    /// détenteur est enregistré dans l'historique et émet un événement [`Event::FIRST_PLACE_TAKEN`].
+    /// Un verrou consultatif par beatmap, pris avant toute lecture, sérialise les synchronisations
+    /// concurrentes : chacune lit le classement et la première place laissés par la précédente.
+    pub async fn sync(conn: &mut PgConnection, beatmap_id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            SELECT pg_advisory_xact_lock($1::bigint)
+            "#,
+            beatmap_id as i64
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let top = sqlx::query!(
+            r#"
+            SELECT user_id, score_id FROM user_beatmap_best
+            WHERE beatmap_id = $1 AND mods = $2 AND criteria = $3
+            ORDER BY value DESC, score DESC, score_id
+            LIMIT 1
+            "#,
+            beatmap_id,
+            ALL_MODS,
+            FIRST_PLACE_CRITERIA
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let current = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT * FROM beatmap_first_place WHERE beatmap_id = $1
+            "#,
+            beatmap_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(top) = top else {
+            if current.is_some() {
+                sqlx::query!("DELETE FROM beatmap_first_place WHERE beatmap_id = $1", beatmap_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            return Ok(());
+        };
+
+        // Même détenteur : seul le score change (amélioration), sans nouvel historique
+        if let Some(current) = current.as_ref().filter(|current| current.user_id == top.user_id) {
+            if current.score_id != Some(top.score_id) {
+                sqlx::query!(
+                    "UPDATE beatmap_first_place SET score_id = $2 WHERE beatmap_id = $1",
+                    beatmap_id,
+                    top.score_id
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            return Ok(());
+        }
+
+        // L'ancien score est toujours visible : la place a été prise par un meilleur score
+        let previous_score_id = current.as_ref().and_then(|current| current.score_id);
+        let sniped = match previous_score_id {
+            Some(score_id) => sqlx::query_scalar!("SELECT NOT hidden FROM score WHERE id = $1", score_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .flatten()
+                .unwrap_or(false),
+            None => false,
+        };
+        let previous_user_id = current.as_ref().map(|current| current.user_id);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO beatmap_first_place (beatmap_id, user_id, score_id, since)
+            VALUES ($1, $2, $3, now())
+            ON CONFLICT (beatmap_id) DO UPDATE
+            SET user_id = EXCLUDED.user_id, score_id = EXCLUDED.score_id, since = EXCLUDED.since
+            "#,
+            beatmap_id,
+            top.user_id,
+            top.score_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let change_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO first_place_history (beatmap_id, user_id, score_id, previous_user_id, previous_score_id, sniped)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#,
+            beatmap_id,
+            top.user_id,
+            top.score_id,
+            previous_user_id,
+            previous_score_id,
+            sniped
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Event::emit(conn, Event::FIRST_PLACE_TAKEN, serde_json::json!({
+            "change_id": change_id,
+            "beatmap_id": beatmap_id,
+            "user_id": top.user_id,
+            "score_id": top.score_id,
+            "previous_user_id": previous_user_id,
+            "previous_score_id": previous_score_id,
+            "sniped": sniped,
+        }))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Premières places actuelles d'un utilisateur, les plus récentes d'abord
+    pub async fn get_by_user(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, limit: i64, offset: i64) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT * FROM beatmap_first_place
+            WHERE user_id = $1
+            ORDER BY since DESC, beatmap_id
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+}
+
+impl FirstPlaceChange {
+    /// Premières places perdues par un utilisateur au profit d'un meilleur score, les plus récentes d'abord
+    pub async fn get_sniped_from_user(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, limit: i64, offset: i64) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT h.id, h.beatmap_id, h.user_id, u.username, h.score_id,
+                   h.previous_user_id, pu.username as "previous_username?", h.previous_score_id,
+                   h.sniped, h.created_at
+            FROM first_place_history h
+            JOIN users u ON u.id = h.user_id
+            LEFT JOIN users pu ON pu.id = h.previous_user_id
+            WHERE h.previous_user_id = $1 AND h.sniped
+            ORDER BY h.created_at DESC, h.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Dernières premières places prises par un meilleur score, toutes beatmaps confondues
+    pub async fn get_recent_snipes(pool: &sqlx::Pool<sqlx::Postgres>, limit: i64, offset: i64) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT h.id, h.beatmap_id, h.user_id, u.username, h.score_id,
+                   h.previous_user_id, pu.username as "previous_username?", h.previous_score_id,
+                   h.sniped, h.created_at
+            FROM first_place_history h
+            JOIN users u ON u.id = h.user_id
+            LEFT JOIN users pu ON pu.id = h.previous_user_id
+            WHERE h.sniped
+            ORDER BY h.created_at DESC, h.id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+}
@@ -1,3 +1,8 @@
 pub mod score;
 pub mod score_rating; 
-pub mod score_stats; 
\ No newline at end of file
+pub mod score_stats;
+pub mod skillset;
+pub mod rating_job;
+pub mod user_beatmap_best;
+pub mod first_place;
+pub mod user_score;
\ No newline at end of file
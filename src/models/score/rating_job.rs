@@ -0,0 +1,357 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, BigDecimal};
+use sqlx::PgPool;
+use std::time::Duration;
+use utoipa::ToSchema;
+use tracing::{error, info, warn};
+use crate::helpers::rating::RatingRegistry;
+use crate::models::map::beatmap::Beatmap;
+use crate::models::score::score::Score;
+use crate::models::score::score_rating::ScoreRating;
+
+/// Nombre de scores traités entre deux sauvegardes de la progression
+const BATCH_SIZE: i64 = 100;
+/// Délai entre deux recherches de job lorsque la file est vide
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Job de recalcul des ratings
+///
+/// Le curseur `cursor_score_id` est sauvegardé après chaque lot : un job interrompu
+/// (redémarrage du serveur) reprend là où il s'était arrêté.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RatingRecalcJob {
+    pub id: i32,
+    /// pending, running, completed, failed ou cancelled
+    pub status: String,
+    pub rating_type: Option<String>,
+    pub mode: Option<i32>,
+    pub beatmap_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub only_outdated: bool,
+    pub cursor_score_id: i32,
+    pub total_scores: i32,
+    pub processed_scores: i32,
+    pub failed_scores: i32,
+    pub error: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: Option<NaiveDateTime>,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct CreateRatingRecalcJob {
+    /// Type de rating à recalculer (par défaut : tous les types actifs)
+    pub rating_type: Option<String>,
+    pub mode: Option<i32>,
+    pub beatmap_id: Option<i32>,
+    pub user_id: Option<i32>,
+    /// Ne recalculer que les ratings absents ou produits par une ancienne version du calculateur
+    #[serde(default)]
+    pub only_outdated: bool,
+}
+
+/// Écart de rating d'un score entre avant et après un recalcul
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RatingDelta {
+    pub score_id: i32,
+    pub user_id: i32,
+    pub beatmap_id: i32,
+    pub rating_type: String,
+    pub old_value: BigDecimal,
+    pub new_value: BigDecimal,
+    pub delta: BigDecimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RatingDeltaSchema {
+    pub score_id: i32,
+    pub user_id: i32,
+    pub beatmap_id: i32,
+    pub rating_type: String,
+    pub old_value: f64,
+    pub new_value: f64,
+    pub delta: f64,
+}
+
+impl RatingDelta {
+    pub fn to_schema(&self) -> RatingDeltaSchema {
+        RatingDeltaSchema {
+            score_id: self.score_id,
+            user_id: self.user_id,
+            beatmap_id: self.beatmap_id,
+            rating_type: self.rating_type.clone(),
+            old_value: self.old_value.to_string().parse::<f64>().unwrap_or(0.0),
+            new_value: self.new_value.to_string().parse::<f64>().unwrap_or(0.0),
+            delta: self.delta.to_string().parse::<f64>().unwrap_or(0.0),
+        }
+    }
+}
+
+impl RatingRecalcJob {
+    /// Lance le worker qui traite les jobs un par un, par ordre de création
+    pub async fn init(pool: PgPool) -> anyhow::Result<()> {
+        tokio::spawn(async move {
+            loop {
+                match Self::next_runnable(&pool).await {
+                    Ok(Some(job)) => {
+                        let id = job.id;
+                        if let Err(e) = job.run(&pool).await {
+                            error!("Rating job {}: échec: {}", id, e);
+                            if let Err(e) = Self::finish(&pool, id, "failed", Some(&e.to_string())).await {
+                                error!("Rating job {}: impossible de marquer le job en échec: {}", id, e);
+                            }
+                            // Si le job n'a pas pu être marqué en échec, il serait repris aussitôt
+                            tokio::time::sleep(POLL_INTERVAL).await;
+                        }
+                    }
+                    Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                    Err(e) => {
+                        error!("Rating jobs: échec de la récupération du prochain job: {}", e);
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub async fn create(pool: &sqlx::Pool<sqlx::Postgres>, create_job: CreateRatingRecalcJob, created_by: i32) -> Result<Self, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO rating_recalc_job (rating_type, mode, beatmap_id, user_id, only_outdated, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+            create_job.rating_type,
+            create_job.mode,
+            create_job.beatmap_id,
+            create_job.user_id,
+            create_job.only_outdated,
+            created_by
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn get_by_id(pool: &sqlx::Pool<sqlx::Postgres>, id: i32) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT * FROM rating_recalc_job WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn get_all(pool: &sqlx::Pool<sqlx::Postgres>, limit: i64, offset: i64) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT * FROM rating_recalc_job ORDER BY id DESC LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Annule un job en attente ou en cours ; retourne `None` s'il n'est plus annulable
+    pub async fn cancel(pool: &sqlx::Pool<sqlx::Postgres>, id: i32) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            UPDATE rating_recalc_job
+            SET status = 'cancelled', finished_at = now(), updated_at = now()
+            WHERE id = $1 AND status IN ('pending', 'running')
+            RETURNING *
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Plus gros écarts (en valeur absolue) constatés par un job
+    pub async fn get_deltas(pool: &sqlx::Pool<sqlx::Postgres>, id: i32, limit: i64) -> Result<Vec<RatingDelta>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            RatingDelta,
+            r#"
+            SELECT d.score_id, s.user_id, s.beatmap_id, rt.name as rating_type,
+                   d.old_value, d.new_value, (d.new_value - d.old_value) as "delta!"
+            FROM rating_recalc_delta d
+            JOIN score s ON s.id = d.score_id
+            JOIN rating_type rt ON rt.id = d.rating_type_id
+            WHERE d.job_id = $1
+            ORDER BY abs(d.new_value - d.old_value) DESC
+            LIMIT $2
+            "#,
+            id,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Job en cours (à reprendre) en priorité, sinon le plus ancien job en attente
+    async fn next_runnable(pool: &sqlx::Pool<sqlx::Postgres>) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT * FROM rating_recalc_job
+            WHERE status IN ('pending', 'running')
+            ORDER BY (status = 'running') DESC, id ASC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    async fn finish(pool: &sqlx::Pool<sqlx::Postgres>, id: i32, status: &str, error: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE rating_recalc_job
+            SET status = $2, error = $3, finished_at = now(), updated_at = now()
+            WHERE id = $1 AND status IN ('pending', 'running')
+            "#,
+            id,
+            status,
+            error
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Traite le job lot par lot jusqu'à la fin ou jusqu'à son annulation
+    async fn run(self, pool: &PgPool) -> anyhow::Result<()> {
+        let mut registry = RatingRegistry::load(pool).await?;
+        if let Some(rating_type) = &self.rating_type {
+            registry = registry.only(rating_type);
+        }
+        if registry.is_empty() {
+            anyhow::bail!("Aucun calculateur actif pour ce job");
+        }
+
+        if self.status == "pending" {
+            let total = Score::count_filtered(pool, self.mode, self.beatmap_id, self.user_id).await?;
+            sqlx::query!(
+                r#"
+                UPDATE rating_recalc_job
+                SET status = 'running', total_scores = $2, started_at = now(), updated_at = now()
+                WHERE id = $1 AND status = 'pending'
+                "#,
+                self.id,
+                total as i32
+            )
+            .execute(pool)
+            .await?;
+            info!("Rating job {}: démarrage sur {} scores", self.id, total);
+        } else {
+            info!("Rating job {}: reprise après le score {}", self.id, self.cursor_score_id);
+        }
+
+        loop {
+            // Recharger le job pour prendre en compte une éventuelle annulation
+            let Some(job) = Self::get_by_id(pool, self.id).await? else { return Ok(()) };
+            if job.status != "running" {
+                info!("Rating job {}: arrêté (statut {})", job.id, job.status);
+                return Ok(());
+            }
+
+            let scores = Score::get_batch_after(pool, job.cursor_score_id, job.mode, job.beatmap_id, job.user_id, BATCH_SIZE).await?;
+            let Some(last) = scores.last() else {
+                Self::finish(pool, job.id, "completed", None).await?;
+                info!("Rating job {}: terminé ({} traités, {} échecs)", job.id, job.processed_scores, job.failed_scores);
+                return Ok(());
+            };
+            let cursor = last.id;
+
+            let mut failed = 0;
+            for score in &scores {
+                if !job.process_score(pool, &registry, score).await {
+                    failed += 1;
+                }
+            }
+
+            sqlx::query!(
+                r#"
+                UPDATE rating_recalc_job
+                SET cursor_score_id = $2,
+                    processed_scores = processed_scores + $3,
+                    failed_scores = failed_scores + $4,
+                    updated_at = now()
+                WHERE id = $1
+                "#,
+                job.id,
+                cursor,
+                (scores.len() - failed) as i32,
+                failed as i32
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    /// Recalcule les ratings d'un score et enregistre les écarts ; retourne `false` en cas d'échec
+    async fn process_score(&self, pool: &PgPool, registry: &RatingRegistry, score: &Score) -> bool {
+        let beatmap = match Beatmap::get_by_id(pool, score.beatmap_id).await {
+            Ok(Some(beatmap)) => beatmap,
+            _ => {
+                warn!("Rating job {}: beatmap {} introuvable pour le score {}", self.id, score.beatmap_id, score.id);
+                return false;
+            }
+        };
+
+        let before = ScoreRating::get_by_score(pool, score.id).await.unwrap_or_default();
+        let failed = registry.recompute(pool, score, &beatmap, self.only_outdated).await;
+        let after = ScoreRating::get_by_score(pool, score.id).await.unwrap_or_default();
+
+        for new in &after {
+            let Some(old) = before.iter().find(|old| old.rating_type_id == new.rating_type_id) else { continue };
+            if old.rating_value == new.rating_value {
+                continue;
+            }
+
+            let inserted = sqlx::query!(
+                r#"
+                INSERT INTO rating_recalc_delta (job_id, score_id, rating_type_id, old_value, new_value)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                self.id,
+                score.id,
+                new.rating_type_id,
+                old.rating_value,
+                new.rating_value
+            )
+            .execute(pool)
+            .await;
+
+            if let Err(e) = inserted {
+                error!("Rating job {}: échec de l'enregistrement de l'écart du score {}: {}", self.id, score.id, e);
+            }
+        }
+
+        failed == 0
+    }
+}
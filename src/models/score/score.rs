@@ -5,6 +5,9 @@ use fake::{Fake, Faker, Dummy};
 use utoipa::ToSchema;
 use crate::models::user::user::{SimplfiedUser};
 use crate::models::map::beatmap::Beatmap;
+use crate::models::score::score_rating::ScoreRatingSchema;
+use crate::models::score::skillset::UserSkillset;
+use crate::models::score::user_beatmap_best::{UserBeatmapBest, ALL_MODS};
 #[derive(Debug, Serialize, Deserialize, Dummy, ToSchema, Clone)]
 pub struct ScoreStatistics {
     pub count_300: i32,
@@ -42,9 +45,11 @@ pub struct Score {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub hash: Option<String>,
+    /// Score masqué : conservé mais exclu des classements
+    pub hidden: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Leaderboard{
     pub id: i32,
     pub beatmap_id: i32,
@@ -60,6 +65,45 @@ pub struct Leaderboard{
     pub updated_at: Option<NaiveDateTime>,
     pub hash: Option<String>,
     pub player : SimplfiedUser,
+    /// Position dans le classement (après filtre pays/amis)
+    pub position: i64,
+}
+
+/// Critère de classement d'un leaderboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardSort {
+    Score,
+    Pp,
+    Accuracy,
+    Combo,
+    Date,
+    Quaver,
+}
+
+impl LeaderboardSort {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "score" => Some(Self::Score),
+            "pp" => Some(Self::Pp),
+            "accuracy" => Some(Self::Accuracy),
+            "combo" => Some(Self::Combo),
+            "date" => Some(Self::Date),
+            "quaver" => Some(Self::Quaver),
+            _ => None,
+        }
+    }
+
+    /// Critère correspondant dans `user_beatmap_best`
+    pub fn criteria(&self) -> &'static str {
+        match self {
+            Self::Score => "score",
+            Self::Pp => "pp",
+            Self::Accuracy => "accuracy",
+            Self::Combo => "combo",
+            Self::Date => "date",
+            Self::Quaver => "quaver",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -78,6 +122,50 @@ pub struct LeaderboardSchema {
     pub updated_at: Option<NaiveDateTime>,
     pub hash: Option<String>,
     pub player: SimplfiedUser,
+    pub position: i64,
+}
+
+/// Page d'un classement, avec le meilleur score de l'utilisateur connecté
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LeaderboardPage {
+    pub scores: Vec<Leaderboard>,
+    /// Meilleur score de l'utilisateur et sa position, même hors de la page
+    pub user_best: Option<Leaderboard>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LeaderboardPageSchema {
+    pub scores: Vec<LeaderboardSchema>,
+    pub user_best: Option<LeaderboardSchema>,
+}
+
+/// Position d'un joueur dans le classement d'une beatmap
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LeaderboardStanding {
+    /// Meilleur score du joueur, avec sa position
+    pub best: Leaderboard,
+    /// Nombre de joueurs classés (après filtres)
+    pub total_players: i64,
+    /// Pourcentage des joueurs classés à la position du joueur ou derrière lui (100 pour le premier)
+    pub percentile: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LeaderboardStandingSchema {
+    pub best: LeaderboardSchema,
+    pub total_players: i64,
+    pub percentile: f64,
+}
+
+/// Filtres d'un classement
+#[derive(Debug, Clone)]
+pub struct LeaderboardFilter {
+    pub mods: Option<i32>,
+    pub sort: LeaderboardSort,
+    /// Restreint aux joueurs de ce pays
+    pub country: Option<String>,
+    /// Restreint à cet utilisateur et aux joueurs qu'il suit
+    pub friends_of: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -96,6 +184,7 @@ pub struct ScoreSchema {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub hash: Option<String>,
+    pub hidden: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Dummy, Clone)]
@@ -113,7 +202,36 @@ pub struct CreateScore {
     pub hash: Option<String>,
 }
 
+/// Détail d'un score avec ses ratings
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScoreDetailSchema {
+    pub score: ScoreSchema,
+    pub ratings: Vec<ScoreRatingSchema>,
+    /// pp qu'aurait rapporté le score sans miss et avec le combo maximum
+    pub if_fc_pp: Option<f64>,
+}
+
 impl Score {
+    pub fn to_schema(&self) -> ScoreSchema {
+        ScoreSchema {
+            id: self.id,
+            user_id: self.user_id,
+            beatmap_id: self.beatmap_id,
+            score: self.score,
+            max_combo: self.max_combo,
+            perfect: self.perfect,
+            statistics: self.statistics.clone(),
+            mods: self.mods,
+            accuracy: self.accuracy.to_string().parse::<f64>().unwrap_or(0.0),
+            rank: self.rank.clone(),
+            replay_available: self.replay_available,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            hash: self.hash.clone(),
+            hidden: self.hidden,
+        }
+    }
+
     pub async fn get_by_id(pool: &sqlx::Pool<sqlx::Postgres>, id: i32) -> Result<Option<Self>, sqlx::Error> {
         let record = sqlx::query_as!(
             Self,
@@ -146,6 +264,64 @@ impl Score {
         Ok(records)
     }
 
+    /// Récupère les scores d'id supérieur à `after_id`, par ordre d'id, avec des filtres optionnels
+    pub async fn get_batch_after(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        after_id: i32,
+        mode: Option<i32>,
+        beatmap_id: Option<i32>,
+        user_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT s.* FROM score s
+            JOIN beatmap b ON b.id = s.beatmap_id
+            WHERE s.id > $1
+            AND ($2::integer IS NULL OR b.mode = $2)
+            AND ($3::integer IS NULL OR s.beatmap_id = $3)
+            AND ($4::integer IS NULL OR s.user_id = $4)
+            ORDER BY s.id
+            LIMIT $5
+            "#,
+            after_id,
+            mode,
+            beatmap_id,
+            user_id,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Compte les scores correspondant aux filtres de [`Self::get_batch_after`]
+    pub async fn count_filtered(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        mode: Option<i32>,
+        beatmap_id: Option<i32>,
+        user_id: Option<i32>,
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM score s
+            JOIN beatmap b ON b.id = s.beatmap_id
+            WHERE ($1::integer IS NULL OR b.mode = $1)
+            AND ($2::integer IS NULL OR s.beatmap_id = $2)
+            AND ($3::integer IS NULL OR s.user_id = $3)
+            "#,
+            mode,
+            beatmap_id,
+            user_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
     pub async fn get_by_user(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32) -> Result<Vec<Self>, sqlx::Error> {
         let records = sqlx::query_as!(
             Self,
@@ -181,7 +357,7 @@ impl Score {
             SELECT s.* FROM score s
             JOIN score_rating sr ON s.id = sr.score_id
             JOIN rating_type rt ON sr.rating_type_id = rt.id
-            WHERE s.user_id = $1 AND rt.name = 'pp'
+            WHERE s.user_id = $1 AND rt.name = 'pp' AND NOT s.hidden
             ORDER BY sr.rating_value DESC
             LIMIT $2
             "#,
@@ -198,13 +374,13 @@ impl Score {
         let record = sqlx::query_as!(
             Self,
             r#"
-            SELECT * FROM score 
-            WHERE user_id = $1 AND beatmap_id = $2 
-            ORDER BY score DESC 
-            LIMIT 1
+            SELECT s.* FROM user_beatmap_best best
+            JOIN score s ON s.id = best.score_id
+            WHERE best.user_id = $1 AND best.beatmap_id = $2 AND best.mods = $3 AND best.criteria = 'score'
             "#,
             user_id,
-            beatmap_id
+            beatmap_id,
+            ALL_MODS
         )
         .fetch_optional(pool)
         .await?;
@@ -212,129 +388,162 @@ impl Score {
         Ok(record)
     }
 
-    pub async fn get_leaderboard(pool: &sqlx::Pool<sqlx::Postgres>, beatmap_id: i32, mods: Option<i32>, page: i64, per_page: i64) -> Result<Vec<Leaderboard>, sqlx::Error> {
-        let offset = (page - 1) * per_page;
-        
-        let records = match mods {
-            Some(mods_value) => {
-                sqlx::query_as!(
-                    Leaderboard,
-                    r#"
-                        SELECT 
-                            best_scores.id,
-                            best_scores.beatmap_id,
-                            best_scores.score,
-                            best_scores.max_combo,
-                            best_scores.perfect,
-                            best_scores.statistics AS "statistics!: JsonValue",
-                            best_scores.mods,
-                            best_scores.accuracy,
-                            best_scores.rank,
-                            best_scores.replay_available,
-                            best_scores.created_at,
-                            best_scores.updated_at,
-                            best_scores.hash,
-                            json_build_object(
-                                'id', u.id,
-                                'username', u.username,
-                                'country', u.country
-                            ) as "player!: JsonValue"
-                        FROM (
-                            SELECT DISTINCT ON (user_id)
-                                id,
-                                user_id,
-                                beatmap_id,
-                                score,
-                                max_combo,
-                                perfect,
-                                statistics,
-                                mods,
-                                accuracy,
-                                rank,
-                                replay_available,
-                                created_at,
-                                updated_at,
-                                hash
-                            FROM score
-                            WHERE beatmap_id = $1 AND mods = $2
-                            ORDER BY user_id, score DESC
-                        ) AS best_scores
-                        JOIN users u ON best_scores.user_id = u.id
-                        ORDER BY best_scores.score DESC
-                        LIMIT $3 OFFSET $4
-
-                    "#,
-                    beatmap_id,
-                    mods_value,
-                    per_page,
-                    offset
-                )
-                .fetch_all(pool)
-                .await?
-            },
-            None => {
-                sqlx::query_as!(
-                    Leaderboard,
-                    r#"
-                        SELECT 
-                            best_scores.id,
-                            best_scores.beatmap_id,
-                            best_scores.score,
-                            best_scores.max_combo,
-                            best_scores.perfect,
-                            best_scores.statistics AS "statistics!: JsonValue",
-                            best_scores.mods,
-                            best_scores.accuracy,
-                            best_scores.rank,
-                            best_scores.replay_available,
-                            best_scores.created_at,
-                            best_scores.updated_at,
-                            best_scores.hash,
-                            json_build_object(
-                                'id', u.id,
-                                'username', u.username,
-                                'country', u.country
-                            ) as "player!: JsonValue"
-                        FROM (
-                            SELECT DISTINCT ON (user_id)
-                                id,
-                                user_id,
-                                beatmap_id,
-                                score,
-                                max_combo,
-                                perfect,
-                                statistics,
-                                mods,
-                                accuracy,
-                                rank,
-                                replay_available,
-                                created_at,
-                                updated_at,
-                                hash
-                            FROM score
-                            WHERE beatmap_id = $1 
-                            ORDER BY user_id, score DESC
-                        ) AS best_scores
-                        JOIN users u ON best_scores.user_id = u.id
-                        ORDER BY best_scores.score DESC
-                        LIMIT $2 OFFSET $3
-                    "#,
-                    beatmap_id,
-                    per_page,
-                    offset
+    /// Lignes classées d'une beatmap, avec le nombre total de joueurs classés
+    ///
+    /// Requête commune à [`Score::get_leaderboard`] et [`Score::get_leaderboard_standing`] :
+    /// retourne les positions de `offset + 1` à `offset + limit`, plus le meilleur score de
+    /// `user_id` quelle que soit sa position.
+    async fn get_ranked(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        beatmap_id: i32,
+        filter: &LeaderboardFilter,
+        offset: i64,
+        limit: i64,
+        user_id: Option<i32>
+    ) -> Result<Vec<(Leaderboard, i64)>, sqlx::Error> {
+        let records = sqlx::query!(
+            r#"
+                WITH ranked AS (
+                    SELECT
+                        best.score_id,
+                        best.user_id,
+                        u.username,
+                        u.country,
+                        ROW_NUMBER() OVER (ORDER BY best.value DESC, best.score DESC, best.score_id) AS position,
+                        COUNT(*) OVER () AS total_players
+                    FROM user_beatmap_best best
+                    JOIN users u ON u.id = best.user_id
+                    WHERE best.beatmap_id = $1
+                    AND best.mods = $2
+                    AND best.criteria = $3
+                    AND ($4::varchar IS NULL OR u.country = $4)
+                    AND ($5::integer IS NULL OR u.id = $5 OR u.id IN (
+                        SELECT followed_id FROM user_follow WHERE follower_id = $5
+                    ))
                 )
-                .fetch_all(pool)
-                .await?
+                SELECT 
+                    s.id,
+                    s.beatmap_id,
+                    s.score,
+                    s.max_combo,
+                    s.perfect,
+                    s.statistics AS "statistics!: JsonValue",
+                    s.mods,
+                    s.accuracy,
+                    s.rank,
+                    s.replay_available,
+                    s.created_at,
+                    s.updated_at,
+                    s.hash,
+                    json_build_object(
+                        'id', ranked.user_id,
+                        'username', ranked.username,
+                        'country', ranked.country
+                    ) as "player!: JsonValue",
+                    ranked.position AS "position!",
+                    ranked.total_players AS "total_players!"
+                FROM ranked
+                JOIN score s ON s.id = ranked.score_id
+                WHERE (ranked.position > $6 AND ranked.position <= $6 + $7)
+                OR ranked.user_id = $8
+                ORDER BY ranked.position
+            "#,
+            beatmap_id,
+            filter.mods.unwrap_or(ALL_MODS),
+            filter.sort.criteria(),
+            filter.country,
+            filter.friends_of,
+            offset,
+            limit,
+            user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records.into_iter().map(|record| {
+            let leaderboard = Leaderboard {
+                id: record.id,
+                beatmap_id: record.beatmap_id,
+                score: record.score,
+                max_combo: record.max_combo,
+                perfect: record.perfect,
+                statistics: record.statistics.into(),
+                mods: record.mods,
+                accuracy: record.accuracy,
+                rank: record.rank,
+                replay_available: record.replay_available,
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+                hash: record.hash,
+                player: record.player.into(),
+                position: record.position,
+            };
+            (leaderboard, record.total_players)
+        }).collect())
+    }
+
+    /// Classement d'une beatmap : meilleur score de chaque joueur selon le critère de tri
+    ///
+    /// Lu depuis `user_beatmap_best` : le même critère sert à choisir le meilleur score
+    /// de chaque joueur et à les ordonner. Les égalités sont départagées par le score puis
+    /// par l'ancienneté (id croissant). Pour un tri par rating, seuls les scores ayant ce
+    /// rating sont classés. Le meilleur score de `viewer_id` est retourné avec sa position,
+    /// même hors de la page.
+    pub async fn get_leaderboard(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        beatmap_id: i32,
+        filter: &LeaderboardFilter,
+        viewer_id: Option<i32>,
+        page: i64,
+        per_page: i64
+    ) -> Result<LeaderboardPage, sqlx::Error> {
+        let offset = (page - 1) * per_page;
+        let records = Self::get_ranked(pool, beatmap_id, filter, offset, per_page, viewer_id).await?;
+
+        let mut scores = Vec::with_capacity(records.len());
+        let mut user_best = None;
+        for (record, _) in records {
+            let in_page = record.position > offset && record.position <= offset + per_page;
+            if Some(record.player.id) == viewer_id {
+                if in_page {
+                    user_best = Some(record.clone());
+                } else {
+                    user_best = Some(record);
+                    continue;
+                }
             }
-        };
-        
-        Ok(records)
+            scores.push(record);
+        }
+
+        Ok(LeaderboardPage { scores, user_best })
+    }
+
+    /// Position d'un joueur dans le classement d'une beatmap, avec les mêmes filtres que [`Score::get_leaderboard`]
+    ///
+    /// Retourne `None` si le joueur n'a aucun score classé avec ces filtres.
+    pub async fn get_leaderboard_standing(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        beatmap_id: i32,
+        filter: &LeaderboardFilter,
+        user_id: i32
+    ) -> Result<Option<LeaderboardStanding>, sqlx::Error> {
+        let records = Self::get_ranked(pool, beatmap_id, filter, 0, 0, Some(user_id)).await?;
+
+        Ok(records.into_iter().next().map(|(best, total_players)| {
+            let percentile = (total_players - best.position + 1) as f64 * 100.0 / total_players as f64;
+            LeaderboardStanding {
+                best,
+                total_players,
+                percentile: (percentile * 100.0).round() / 100.0,
+            }
+        }))
     }
 
     pub async fn create(pool: &sqlx::Pool<sqlx::Postgres>, create_score: CreateScore) -> Result<Self, sqlx::Error> {
         let statistics_json = serde_json::to_value(create_score.statistics)
             .map_err(|e| sqlx::Error::Protocol(format!("Failed to serialize statistics: {}", e)))?;
         let get_beatmap = Beatmap::get_by_md5(pool, &create_score.beatmap_hash).await?.unwrap();
+        let mut tx = pool.begin().await?;
         let record: Score = sqlx::query_as!(
             Self,
             r#"
@@ -357,9 +566,65 @@ impl Score {
             create_score.replay_available,
             create_score.hash
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        UserBeatmapBest::refresh(&mut tx, record.user_id, record.beatmap_id).await?;
+        tx.commit().await?;
+
+        Ok(record)
+    }
+
+    /// Masque ou réaffiche un score, en mettant à jour les meilleurs scores et les skillsets du joueur
+    ///
+    /// Un score masqué est retiré des scores épinglés du joueur.
+    pub async fn set_hidden(pool: &sqlx::Pool<sqlx::Postgres>, id: i32, hidden: bool) -> Result<Option<Self>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            UPDATE score SET hidden = $2, updated_at = now()
+            WHERE id = $1
+            RETURNING *
+            "#,
+            id,
+            hidden
+        )
+        .fetch_optional(&mut *tx)
         .await?;
 
+        if let Some(score) = &record {
+            if hidden {
+                sqlx::query!("DELETE FROM user_pinned_score WHERE score_id = $1", score.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            UserBeatmapBest::refresh(&mut tx, score.user_id, score.beatmap_id).await?;
+            UserSkillset::refresh_existing(&mut tx, score.user_id).await?;
+        }
+        tx.commit().await?;
+
         Ok(record)
     }
+
+    /// Supprime un score et ses ratings ; retourne `false` si le score n'existe pas
+    pub async fn delete(pool: &sqlx::Pool<sqlx::Postgres>, id: i32) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let deleted = sqlx::query!(
+            r#"
+            DELETE FROM score WHERE id = $1
+            RETURNING user_id, beatmap_id
+            "#,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(deleted) = deleted else { return Ok(false) };
+        UserBeatmapBest::refresh(&mut tx, deleted.user_id, deleted.beatmap_id).await?;
+        UserSkillset::refresh_existing(&mut tx, deleted.user_id).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
 }
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use sqlx::types::{chrono::NaiveDateTime, BigDecimal};
+use sqlx::types::{chrono::NaiveDateTime, BigDecimal, JsonValue};
+use utoipa::ToSchema;
+use crate::models::score::user_beatmap_best::UserBeatmapBest;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScoreRating {
@@ -10,6 +12,9 @@ pub struct ScoreRating {
     pub max_rating: Option<BigDecimal>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub calculator_version: i32,
+    pub fc_rating: Option<BigDecimal>,
+    pub attributes: Option<JsonValue>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,12 +27,114 @@ pub struct RatingType {
     pub updated_at: Option<NaiveDateTime>,
 }
 
+/// Rating global d'un utilisateur pour un type de rating
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserOverallRating {
+    pub rating_type: String,
+    pub rating: f64,
+    pub scores_count: i64,
+}
+
+/// Profil de compétences d'un utilisateur
+///
+/// Moyenne pondérée (0.95^n) des composantes du pp de ses 100 meilleurs scores,
+/// un par beatmap. Les composantes absentes du mode sont à `None`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserSkillProfile {
+    pub mode: i32,
+    pub aim: Option<f64>,
+    pub speed: Option<f64>,
+    pub accuracy: Option<f64>,
+    pub flashlight: Option<f64>,
+    /// Composante de difficulté (taiko, mania)
+    pub difficulty: Option<f64>,
+    pub scores_count: i64,
+}
+
+/// Rating d'un score avec le nom de son type
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamedScoreRating {
+    pub rating_type: String,
+    pub rating_value: BigDecimal,
+    pub max_rating: Option<BigDecimal>,
+    pub fc_rating: Option<BigDecimal>,
+    pub calculator_version: i32,
+    pub attributes: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScoreRatingSchema {
+    pub rating_type: String,
+    pub rating_value: f64,
+    /// Rating d'un SS avec les mêmes mods
+    pub max_rating: Option<f64>,
+    /// Rating en full combo (misses comptés comme des 300)
+    pub fc_rating: Option<f64>,
+    pub calculator_version: i32,
+    /// Détail du rating propre au système (ex. composantes aim/speed/accuracy/flashlight du pp)
+    pub attributes: Option<JsonValue>,
+}
+
+impl NamedScoreRating {
+    pub fn to_schema(&self) -> ScoreRatingSchema {
+        ScoreRatingSchema {
+            rating_type: self.rating_type.clone(),
+            rating_value: self.rating_value.to_string().parse::<f64>().unwrap_or(0.0),
+            max_rating: self.max_rating.as_ref().map(|m| m.to_string().parse::<f64>().unwrap_or(0.0)),
+            fc_rating: self.fc_rating.as_ref().map(|m| m.to_string().parse::<f64>().unwrap_or(0.0)),
+            calculator_version: self.calculator_version,
+            attributes: self.attributes.clone(),
+        }
+    }
+}
+
+/// Score dont le pp est nettement inférieur à son pp en full combo
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScoreChoke {
+    pub score_id: i32,
+    pub beatmap_id: i32,
+    pub mods: i32,
+    pub pp: BigDecimal,
+    pub fc_pp: BigDecimal,
+    pub ss_pp: Option<BigDecimal>,
+    pub pp_lost: BigDecimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScoreChokeSchema {
+    pub score_id: i32,
+    pub beatmap_id: i32,
+    pub mods: i32,
+    pub pp: f64,
+    pub fc_pp: f64,
+    pub ss_pp: Option<f64>,
+    /// pp perdu à cause des misses et du combo (fc_pp - pp)
+    pub pp_lost: f64,
+}
+
+impl ScoreChoke {
+    pub fn to_schema(&self) -> ScoreChokeSchema {
+        ScoreChokeSchema {
+            score_id: self.score_id,
+            beatmap_id: self.beatmap_id,
+            mods: self.mods,
+            pp: self.pp.to_string().parse::<f64>().unwrap_or(0.0),
+            fc_pp: self.fc_pp.to_string().parse::<f64>().unwrap_or(0.0),
+            ss_pp: self.ss_pp.as_ref().map(|m| m.to_string().parse::<f64>().unwrap_or(0.0)),
+            pp_lost: self.pp_lost.to_string().parse::<f64>().unwrap_or(0.0),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateScoreRating {
     pub score_id: i32,
     pub rating_type_id: i32,
     pub rating_value: BigDecimal,
     pub max_rating: Option<BigDecimal>,
+    pub fc_rating: Option<BigDecimal>,
+    pub calculator_version: i32,
+    pub attributes: Option<JsonValue>,
 }
 
 impl ScoreRating {
@@ -59,6 +166,25 @@ impl ScoreRating {
         Ok(records)
     }
 
+    /// Récupère les ratings d'un score avec le nom de leur type
+    pub async fn get_named_by_score(pool: &sqlx::Pool<sqlx::Postgres>, score_id: i32) -> Result<Vec<NamedScoreRating>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            NamedScoreRating,
+            r#"
+            SELECT rt.name as rating_type, sr.rating_value, sr.max_rating, sr.fc_rating, sr.calculator_version, sr.attributes
+            FROM score_rating sr
+            JOIN rating_type rt ON sr.rating_type_id = rt.id
+            WHERE sr.score_id = $1
+            ORDER BY rt.id
+            "#,
+            score_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
     pub async fn get_by_score_and_type(
         pool: &sqlx::Pool<sqlx::Postgres>, 
         score_id: i32, 
@@ -101,24 +227,187 @@ impl ScoreRating {
 
     /// Crée un nouveau score_rating
     pub async fn create(pool: &sqlx::Pool<sqlx::Postgres>, create_rating: CreateScoreRating) -> Result<Self, sqlx::Error> {
+        let mut tx = pool.begin().await?;
         let record = sqlx::query_as!(
             Self,
             r#"
-            INSERT INTO score_rating (score_id, rating_type_id, rating_value, max_rating)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO score_rating (score_id, rating_type_id, rating_value, max_rating, fc_rating, calculator_version, attributes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             RETURNING *
             "#,
             create_rating.score_id,
             create_rating.rating_type_id,
             create_rating.rating_value as _,
-            create_rating.max_rating as Option<BigDecimal>
+            create_rating.max_rating as Option<BigDecimal>,
+            create_rating.fc_rating as Option<BigDecimal>,
+            create_rating.calculator_version,
+            create_rating.attributes
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        UserBeatmapBest::refresh_for_score(&mut tx, record.score_id).await?;
+        tx.commit().await?;
+        Ok(record)
+    }
+
+    /// Remplace le score_rating d'un score pour un type de rating
+    pub async fn replace(pool: &sqlx::Pool<sqlx::Postgres>, create_rating: CreateScoreRating) -> Result<Self, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM score_rating WHERE score_id = $1 AND rating_type_id = $2
+            "#,
+            create_rating.score_id,
+            create_rating.rating_type_id
+        )
+        .execute(&mut *tx)
         .await?;
 
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO score_rating (score_id, rating_type_id, rating_value, max_rating, fc_rating, calculator_version, attributes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            create_rating.score_id,
+            create_rating.rating_type_id,
+            create_rating.rating_value as _,
+            create_rating.max_rating as Option<BigDecimal>,
+            create_rating.fc_rating as Option<BigDecimal>,
+            create_rating.calculator_version,
+            create_rating.attributes
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        UserBeatmapBest::refresh_for_score(&mut tx, record.score_id).await?;
+        tx.commit().await?;
         Ok(record)
     }
 
+    /// Calcule le rating global d'un utilisateur pour un type de rating
+    ///
+    /// Seul le meilleur rating par beatmap est retenu, puis les 50 meilleurs sont
+    /// moyennés avec une pondération de 0.95^n, comme l'overall rating de Quaver.
+    pub async fn get_user_overall(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        rating_type: &str
+    ) -> Result<UserOverallRating, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"
+            WITH best AS (
+                SELECT DISTINCT ON (s.beatmap_id) sr.rating_value
+                FROM score s
+                JOIN score_rating sr ON sr.score_id = s.id
+                JOIN rating_type rt ON rt.id = sr.rating_type_id
+                WHERE s.user_id = $1 AND rt.name = $2 AND NOT s.hidden
+                ORDER BY s.beatmap_id, sr.rating_value DESC
+            ),
+            top AS (
+                SELECT rating_value, ROW_NUMBER() OVER (ORDER BY rating_value DESC) - 1 AS idx
+                FROM best
+                ORDER BY rating_value DESC
+                LIMIT 50
+            )
+            SELECT
+                COALESCE(SUM(rating_value * POWER(0.95, idx)) / NULLIF(SUM(POWER(0.95, idx)), 0), 0)::float8 AS "rating!",
+                (SELECT COUNT(*) FROM best) AS "scores_count!"
+            FROM top
+            "#,
+            user_id,
+            rating_type
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(UserOverallRating {
+            rating_type: rating_type.to_string(),
+            rating: record.rating,
+            scores_count: record.scores_count,
+        })
+    }
+
+    /// Profil de compétences d'un utilisateur pour un mode, à partir des composantes du pp
+    pub async fn get_user_skill_profile(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        mode: i32
+    ) -> Result<UserSkillProfile, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"
+            WITH best AS (
+                SELECT DISTINCT ON (s.beatmap_id) sr.rating_value, sr.attributes
+                FROM score s
+                JOIN beatmap b ON b.id = s.beatmap_id
+                JOIN score_rating sr ON sr.score_id = s.id
+                JOIN rating_type rt ON rt.id = sr.rating_type_id
+                WHERE s.user_id = $1 AND b.mode = $2 AND rt.name = 'pp' AND sr.attributes IS NOT NULL AND NOT s.hidden
+                ORDER BY s.beatmap_id, sr.rating_value DESC
+            ),
+            top AS (
+                SELECT attributes, POWER(0.95, ROW_NUMBER() OVER (ORDER BY rating_value DESC) - 1)::float8 AS weight
+                FROM best
+                ORDER BY rating_value DESC
+                LIMIT 100
+            )
+            SELECT
+                SUM((attributes->>'aim')::float8 * weight) / NULLIF(SUM(weight), 0) AS aim,
+                SUM((attributes->>'speed')::float8 * weight) / NULLIF(SUM(weight), 0) AS speed,
+                SUM((attributes->>'accuracy')::float8 * weight) / NULLIF(SUM(weight), 0) AS accuracy,
+                SUM((attributes->>'flashlight')::float8 * weight) / NULLIF(SUM(weight), 0) AS flashlight,
+                SUM((attributes->>'difficulty')::float8 * weight) / NULLIF(SUM(weight), 0) AS difficulty,
+                COUNT(*) AS "scores_count!"
+            FROM top
+            "#,
+            user_id,
+            mode
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(UserSkillProfile {
+            mode,
+            aim: record.aim,
+            speed: record.speed,
+            accuracy: record.accuracy,
+            flashlight: record.flashlight,
+            difficulty: record.difficulty,
+            scores_count: record.scores_count,
+        })
+    }
+
+    /// Scores d'un utilisateur ayant perdu le plus de pp par rapport à un full combo
+    pub async fn get_user_chokes(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        limit: i64
+    ) -> Result<Vec<ScoreChoke>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            ScoreChoke,
+            r#"
+            SELECT s.id as score_id, s.beatmap_id, s.mods, sr.rating_value as pp,
+                   sr.fc_rating as "fc_pp!", sr.max_rating as ss_pp,
+                   (sr.fc_rating - sr.rating_value) as "pp_lost!"
+            FROM score s
+            JOIN score_rating sr ON sr.score_id = s.id
+            JOIN rating_type rt ON rt.id = sr.rating_type_id
+            WHERE s.user_id = $1 AND rt.name = 'pp' AND sr.fc_rating > sr.rating_value AND NOT s.hidden
+            ORDER BY (sr.fc_rating - sr.rating_value) DESC
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
     /// Récupère tous les scores qui n'ont pas de rating de type "pp"
     pub async fn get_scores_without_pp_rating(
         pool: &sqlx::Pool<sqlx::Postgres>,
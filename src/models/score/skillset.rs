@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, BigDecimal, Json};
+use sqlx::PgConnection;
+use utoipa::ToSchema;
+use crate::helpers::etterna::{aggregate_ssrs, calculate_msd_for_beatmap, ETTERNA_RATING_TYPE, RATE_MODS_MASK};
+use crate::helpers::pp::BeatmapFile;
+
+/// Valeurs des skillsets Etterna (MSD pour une beatmap, rating pour un joueur)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+pub struct Skillsets {
+    pub overall: f64,
+    pub stream: f64,
+    pub jumpstream: f64,
+    pub handstream: f64,
+    pub stamina: f64,
+    pub jackspeed: f64,
+    pub chordjack: f64,
+    pub technical: f64,
+}
+
+impl Skillsets {
+    /// Skillsets hors overall, avec leur nom
+    pub fn skills(&self) -> [(&'static str, f64); 7] {
+        [
+            ("stream", self.stream),
+            ("jumpstream", self.jumpstream),
+            ("handstream", self.handstream),
+            ("stamina", self.stamina),
+            ("jackspeed", self.jackspeed),
+            ("chordjack", self.chordjack),
+            ("technical", self.technical),
+        ]
+    }
+
+    /// Applique un même facteur à tous les skillsets
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            overall: self.overall * factor,
+            stream: self.stream * factor,
+            jumpstream: self.jumpstream * factor,
+            handstream: self.handstream * factor,
+            stamina: self.stamina * factor,
+            jackspeed: self.jackspeed * factor,
+            chordjack: self.chordjack * factor,
+            technical: self.technical * factor,
+        }
+    }
+}
+
+/// Skillsets (MSD) d'une beatmap mania pour une combinaison de mods de vitesse
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BeatmapSkillset {
+    pub id: i32,
+    pub beatmap_id: i32,
+    pub rate_mods: i32,
+    pub overall: BigDecimal,
+    pub skillsets: Json<Skillsets>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+/// Skillsets agrégés d'un joueur
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserSkillset {
+    pub user_id: i32,
+    pub overall: BigDecimal,
+    pub skillsets: Json<Skillsets>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SkillsetSchema {
+    pub rate_mods: i32,
+    pub skillsets: Skillsets,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl BeatmapSkillset {
+    pub fn to_schema(&self) -> SkillsetSchema {
+        SkillsetSchema {
+            rate_mods: self.rate_mods,
+            skillsets: self.skillsets.0,
+            updated_at: self.updated_at,
+        }
+    }
+
+    pub async fn get(pool: &sqlx::Pool<sqlx::Postgres>, beatmap_id: i32, mods: i32) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT id, beatmap_id, rate_mods, overall, skillsets as "skillsets: Json<Skillsets>", created_at, updated_at
+            FROM beatmap_skillset
+            WHERE beatmap_id = $1 AND rate_mods = $2
+            "#,
+            beatmap_id,
+            mods & RATE_MODS_MASK
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn upsert(pool: &sqlx::Pool<sqlx::Postgres>, beatmap_id: i32, mods: i32, skillsets: Skillsets) -> Result<Self, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO beatmap_skillset (beatmap_id, rate_mods, overall, skillsets)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (beatmap_id, rate_mods) DO UPDATE
+            SET overall = EXCLUDED.overall,
+                skillsets = EXCLUDED.skillsets,
+                updated_at = now()
+            RETURNING id, beatmap_id, rate_mods, overall, skillsets as "skillsets: Json<Skillsets>", created_at, updated_at
+            "#,
+            beatmap_id,
+            mods & RATE_MODS_MASK,
+            BigDecimal::try_from(skillsets.overall).unwrap_or_default(),
+            Json(skillsets) as _
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Récupère les skillsets d'une beatmap, en les calculant s'ils ne sont pas encore en base
+    pub async fn get_or_calculate(pool: &sqlx::Pool<sqlx::Postgres>, file: &BeatmapFile<'_>, mods: i32) -> anyhow::Result<Self> {
+        let beatmap_id = file.beatmap().id;
+        if let Some(existing) = Self::get(pool, beatmap_id, mods).await? {
+            return Ok(existing);
+        }
+
+        let skillsets = calculate_msd_for_beatmap(file, mods).await.map_err(anyhow::Error::msg)?;
+        Ok(Self::upsert(pool, beatmap_id, mods, skillsets).await?)
+    }
+}
+
+impl UserSkillset {
+    pub fn to_schema(&self) -> Skillsets {
+        self.skillsets.0
+    }
+
+    pub async fn get_by_user(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT user_id, overall, skillsets as "skillsets: Json<Skillsets>", updated_at
+            FROM user_skillset
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Recalcule les skillsets d'un joueur à partir de ses meilleurs scores Etterna
+    ///
+    /// Le SSR de chaque skillset d'un score est le MSD de la beatmap multiplié par le même
+    /// facteur de précision que le SSR overall enregistré dans `score_rating`.
+    /// Les scores masqués ne sont pas pris en compte.
+    pub async fn recalculate(conn: &mut PgConnection, user_id: i32) -> Result<Self, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT DISTINCT ON (s.beatmap_id, bs.rate_mods)
+                bs.skillsets as "skillsets!: Json<Skillsets>",
+                bs.overall as msd,
+                sr.rating_value as ssr
+            FROM score s
+            JOIN score_rating sr ON sr.score_id = s.id
+            JOIN rating_type rt ON rt.id = sr.rating_type_id AND rt.name = $2
+            JOIN beatmap_skillset bs ON bs.beatmap_id = s.beatmap_id AND bs.rate_mods = (s.mods & $3)
+            WHERE s.user_id = $1 AND NOT s.hidden
+            ORDER BY s.beatmap_id, bs.rate_mods, sr.rating_value DESC
+            "#,
+            user_id,
+            ETTERNA_RATING_TYPE,
+            RATE_MODS_MASK
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let scores: Vec<Skillsets> = rows.into_iter()
+            .filter_map(|row| {
+                let msd = row.msd.to_string().parse::<f64>().ok()?;
+                let ssr = row.ssr.to_string().parse::<f64>().ok()?;
+                (msd > 0.0).then(|| row.skillsets.0.scaled(ssr / msd))
+            })
+            .collect();
+
+        let aggregate = |skill: fn(&Skillsets) -> f64| {
+            aggregate_ssrs(&scores.iter().map(skill).collect::<Vec<_>>())
+        };
+        let skillsets = Skillsets {
+            overall: aggregate(|s| s.overall),
+            stream: aggregate(|s| s.stream),
+            jumpstream: aggregate(|s| s.jumpstream),
+            handstream: aggregate(|s| s.handstream),
+            stamina: aggregate(|s| s.stamina),
+            jackspeed: aggregate(|s| s.jackspeed),
+            chordjack: aggregate(|s| s.chordjack),
+            technical: aggregate(|s| s.technical),
+        };
+
+        let record = sqlx::query_as!(
+            Self,
+            r#"
+            INSERT INTO user_skillset (user_id, overall, skillsets)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET overall = EXCLUDED.overall,
+                skillsets = EXCLUDED.skillsets,
+                updated_at = now()
+            RETURNING user_id, overall, skillsets as "skillsets: Json<Skillsets>", updated_at
+            "#,
+            user_id,
+            BigDecimal::try_from(skillsets.overall).unwrap_or_default(),
+            Json(skillsets) as _
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Recalcule les skillsets d'un joueur s'il en possède déjà (après le masquage ou la suppression d'un score)
+    pub async fn refresh_existing(conn: &mut PgConnection, user_id: i32) -> Result<(), sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM user_skillset WHERE user_id = $1) as "exists!"
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if exists {
+            Self::recalculate(conn, user_id).await?;
+        }
+
+        Ok(())
+    }
+}
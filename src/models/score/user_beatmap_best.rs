@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, BigDecimal};
+use sqlx::PgConnection;
+use crate::models::score::first_place::BeatmapFirstPlace;
+
+/// Combinaison de mods regroupant tous les scores, quels que soient leurs mods
+pub const ALL_MODS: i32 = -1;
+
+/// Meilleur score d'un joueur sur une beatmap, pour une combinaison de mods et un critère
+///
+/// Table dérivée de `score` et `score_rating` : elle doit être rafraîchie via [`UserBeatmapBest::refresh`]
+/// dans la même transaction que toute modification d'un score ou de ses ratings.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserBeatmapBest {
+    pub user_id: i32,
+    pub beatmap_id: i32,
+    pub mode: i32,
+    /// Mods exacts du score, ou [`ALL_MODS`]
+    pub mods: i32,
+    /// score, accuracy, combo, date ou nom d'un rating_type actif
+    pub criteria: String,
+    pub score_id: i32,
+    pub value: BigDecimal,
+    pub score: i32,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl UserBeatmapBest {
+    /// Recalcule les meilleurs scores d'un joueur sur une beatmap, puis la première place de la beatmap
+    ///
+    /// Un verrou consultatif par couple joueur/beatmap sérialise les rafraîchissements concurrents
+    /// jusqu'à la fin de la transaction appelante.
+    pub async fn refresh(conn: &mut PgConnection, user_id: i32, beatmap_id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            SELECT pg_advisory_xact_lock($1, $2)
+            "#,
+            user_id,
+            beatmap_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_beatmap_best WHERE user_id = $1 AND beatmap_id = $2
+            "#,
+            user_id,
+            beatmap_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_beatmap_best (user_id, beatmap_id, mode, mods, criteria, score_id, value, score)
+            SELECT DISTINCT ON (c.mods_bucket, c.criteria)
+                c.user_id, c.beatmap_id, c.mode, c.mods_bucket, c.criteria, c.id, c.value, c.score
+            FROM (
+                SELECT s.id, s.user_id, s.beatmap_id, b.mode, s.score, bucket.mods_bucket, crit.criteria,
+                    CASE crit.criteria
+                        WHEN 'score' THEN s.score::numeric
+                        WHEN 'accuracy' THEN s.accuracy
+                        WHEN 'combo' THEN s.max_combo::numeric
+                        WHEN 'date' THEN extract(epoch FROM s.created_at)::numeric
+                        ELSE (
+                            SELECT sr.rating_value FROM score_rating sr
+                            JOIN rating_type rt ON rt.id = sr.rating_type_id
+                            WHERE sr.score_id = s.id AND rt.name = crit.criteria
+                        )
+                    END AS value
+                FROM score s
+                JOIN beatmap b ON b.id = s.beatmap_id
+                CROSS JOIN LATERAL (VALUES ($3::integer), (s.mods)) AS bucket(mods_bucket)
+                CROSS JOIN (
+                    SELECT unnest(ARRAY['score', 'accuracy', 'combo', 'date']::varchar[]) AS criteria
+                    UNION ALL
+                    SELECT name FROM rating_type WHERE is_active
+                ) AS crit
+                WHERE s.user_id = $1 AND s.beatmap_id = $2 AND NOT s.hidden
+            ) c
+            WHERE c.value IS NOT NULL
+            ORDER BY c.mods_bucket, c.criteria, c.value DESC, c.score DESC, c.id
+            "#,
+            user_id,
+            beatmap_id,
+            ALL_MODS
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        BeatmapFirstPlace::sync(conn, beatmap_id).await
+    }
+
+    /// Recalcule les meilleurs scores du joueur et de la beatmap d'un score (ex. après un nouveau rating)
+    pub async fn refresh_for_score(conn: &mut PgConnection, score_id: i32) -> Result<(), sqlx::Error> {
+        let score = sqlx::query!(
+            r#"
+            SELECT user_id, beatmap_id FROM score WHERE id = $1
+            "#,
+            score_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match score {
+            Some(score) => Self::refresh(conn, score.user_id, score.beatmap_id).await,
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDateTime, BigDecimal, JsonValue};
+use utoipa::ToSchema;
+use crate::models::score::score::ScoreStatistics;
+use crate::models::score::user_beatmap_best::ALL_MODS;
+
+/// Nombre maximum de scores épinglés par utilisateur
+pub const MAX_PINNED_SCORES: i64 = 10;
+
+/// Résultat d'une demande d'épinglage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinResult {
+    Pinned,
+    AlreadyPinned,
+    /// L'utilisateur a déjà [`MAX_PINNED_SCORES`] scores épinglés
+    LimitReached,
+}
+
+/// Beatmap d'un score, avec les informations de sa beatmapset
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ScoreBeatmap {
+    pub id: i32,
+    pub beatmapset_id: i32,
+    pub version: String,
+    pub difficulty_rating: f64,
+    pub mode: i32,
+    pub status: String,
+    pub artist: String,
+    pub title: String,
+    pub creator_id: Option<i32>,
+    pub cover_url: Option<String>,
+}
+
+impl From<JsonValue> for ScoreBeatmap {
+    fn from(value: JsonValue) -> Self {
+        serde_json::from_value(value).unwrap()
+    }
+}
+
+/// Score d'un utilisateur avec sa beatmap et son pp, pour les listes du profil
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserScore {
+    pub id: i32,
+    pub beatmap_id: i32,
+    pub score: i32,
+    pub max_combo: i32,
+    pub perfect: bool,
+    pub statistics: ScoreStatistics,
+    pub mods: i32,
+    pub accuracy: BigDecimal,
+    pub rank: String,
+    pub replay_available: bool,
+    pub created_at: Option<NaiveDateTime>,
+    pub pp: Option<BigDecimal>,
+    pub beatmap: ScoreBeatmap,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserScoreSchema {
+    pub id: i32,
+    pub beatmap_id: i32,
+    pub score: i32,
+    pub max_combo: i32,
+    pub perfect: bool,
+    pub statistics: ScoreStatistics,
+    pub mods: i32,
+    pub accuracy: f64,
+    pub rank: String,
+    pub replay_available: bool,
+    pub created_at: Option<NaiveDateTime>,
+    /// pp du score, absent s'il n'a pas encore été calculé
+    pub pp: Option<f64>,
+    pub beatmap: ScoreBeatmap,
+}
+
+impl UserScore {
+    pub fn to_schema(&self) -> UserScoreSchema {
+        UserScoreSchema {
+            id: self.id,
+            beatmap_id: self.beatmap_id,
+            score: self.score,
+            max_combo: self.max_combo,
+            perfect: self.perfect,
+            statistics: self.statistics.clone(),
+            mods: self.mods,
+            accuracy: self.accuracy.to_string().parse::<f64>().unwrap_or(0.0),
+            rank: self.rank.clone(),
+            replay_available: self.replay_available,
+            created_at: self.created_at,
+            pp: self.pp.as_ref().map(|pp| pp.to_string().parse::<f64>().unwrap_or(0.0)),
+            beatmap: self.beatmap.clone(),
+        }
+    }
+
+    /// Meilleurs scores d'un utilisateur par pp, un par beatmap
+    ///
+    /// Avec `mods`, seuls les scores joués avec exactement ces mods sont considérés.
+    pub async fn get_best(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        mode: i32,
+        mods: Option<i32>,
+        limit: i64,
+        offset: i64
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT s.id, s.beatmap_id, s.score, s.max_combo, s.perfect,
+                   s.statistics AS "statistics!: JsonValue",
+                   s.mods, s.accuracy, s.rank, s.replay_available, s.created_at,
+                   best.value AS "pp?",
+                   json_build_object(
+                       'id', b.id,
+                       'beatmapset_id', b.beatmapset_id,
+                       'version', b.version,
+                       'difficulty_rating', b.difficulty_rating,
+                       'mode', b.mode,
+                       'status', b.status,
+                       'artist', bs.artist,
+                       'title', bs.title,
+                       'creator_id', bs.creator_id,
+                       'cover_url', bs.cover_url
+                   ) AS "beatmap!: JsonValue"
+            FROM user_beatmap_best best
+            JOIN score s ON s.id = best.score_id
+            JOIN beatmap b ON b.id = best.beatmap_id
+            JOIN beatmapset bs ON bs.id = b.beatmapset_id
+            WHERE best.user_id = $1 AND best.mode = $2 AND best.mods = $3 AND best.criteria = 'pp'
+            ORDER BY best.value DESC, best.score_id
+            LIMIT $4 OFFSET $5
+            "#,
+            user_id,
+            mode,
+            mods.unwrap_or(ALL_MODS),
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Derniers scores d'un utilisateur, hors scores masqués
+    pub async fn get_recent(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        mode: i32,
+        mods: Option<i32>,
+        limit: i64,
+        offset: i64
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT s.id, s.beatmap_id, s.score, s.max_combo, s.perfect,
+                   s.statistics AS "statistics!: JsonValue",
+                   s.mods, s.accuracy, s.rank, s.replay_available, s.created_at,
+                   pp.value AS "pp?",
+                   json_build_object(
+                       'id', b.id,
+                       'beatmapset_id', b.beatmapset_id,
+                       'version', b.version,
+                       'difficulty_rating', b.difficulty_rating,
+                       'mode', b.mode,
+                       'status', b.status,
+                       'artist', bs.artist,
+                       'title', bs.title,
+                       'creator_id', bs.creator_id,
+                       'cover_url', bs.cover_url
+                   ) AS "beatmap!: JsonValue"
+            FROM score s
+            JOIN beatmap b ON b.id = s.beatmap_id
+            JOIN beatmapset bs ON bs.id = b.beatmapset_id
+            LEFT JOIN LATERAL (
+                SELECT MAX(sr.rating_value) AS value
+                FROM score_rating sr
+                JOIN rating_type rt ON rt.id = sr.rating_type_id
+                WHERE sr.score_id = s.id AND rt.name = 'pp'
+            ) pp ON true
+            WHERE s.user_id = $1 AND b.mode = $2 AND NOT s.hidden
+            AND ($3::integer IS NULL OR s.mods = $3)
+            ORDER BY s.created_at DESC, s.id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            user_id,
+            mode,
+            mods,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Scores d'un utilisateur actuellement premiers de leur beatmap, les plus récents d'abord
+    pub async fn get_firsts(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        mode: i32,
+        mods: Option<i32>,
+        limit: i64,
+        offset: i64
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT s.id, s.beatmap_id, s.score, s.max_combo, s.perfect,
+                   s.statistics AS "statistics!: JsonValue",
+                   s.mods, s.accuracy, s.rank, s.replay_available, s.created_at,
+                   pp.value AS "pp?",
+                   json_build_object(
+                       'id', b.id,
+                       'beatmapset_id', b.beatmapset_id,
+                       'version', b.version,
+                       'difficulty_rating', b.difficulty_rating,
+                       'mode', b.mode,
+                       'status', b.status,
+                       'artist', bs.artist,
+                       'title', bs.title,
+                       'creator_id', bs.creator_id,
+                       'cover_url', bs.cover_url
+                   ) AS "beatmap!: JsonValue"
+            FROM beatmap_first_place fp
+            JOIN score s ON s.id = fp.score_id
+            JOIN beatmap b ON b.id = fp.beatmap_id
+            JOIN beatmapset bs ON bs.id = b.beatmapset_id
+            LEFT JOIN LATERAL (
+                SELECT MAX(sr.rating_value) AS value
+                FROM score_rating sr
+                JOIN rating_type rt ON rt.id = sr.rating_type_id
+                WHERE sr.score_id = s.id AND rt.name = 'pp'
+            ) pp ON true
+            WHERE fp.user_id = $1 AND b.mode = $2
+            AND ($3::integer IS NULL OR s.mods = $3)
+            ORDER BY fp.since DESC, fp.beatmap_id
+            LIMIT $4 OFFSET $5
+            "#,
+            user_id,
+            mode,
+            mods,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Scores épinglés par un utilisateur, les plus récemment épinglés d'abord
+    pub async fn get_pinned(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        mode: i32,
+        mods: Option<i32>,
+        limit: i64,
+        offset: i64
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT s.id, s.beatmap_id, s.score, s.max_combo, s.perfect,
+                   s.statistics AS "statistics!: JsonValue",
+                   s.mods, s.accuracy, s.rank, s.replay_available, s.created_at,
+                   pp.value AS "pp?",
+                   json_build_object(
+                       'id', b.id,
+                       'beatmapset_id', b.beatmapset_id,
+                       'version', b.version,
+                       'difficulty_rating', b.difficulty_rating,
+                       'mode', b.mode,
+                       'status', b.status,
+                       'artist', bs.artist,
+                       'title', bs.title,
+                       'creator_id', bs.creator_id,
+                       'cover_url', bs.cover_url
+                   ) AS "beatmap!: JsonValue"
+            FROM user_pinned_score pin
+            JOIN score s ON s.id = pin.score_id
+            JOIN beatmap b ON b.id = s.beatmap_id
+            JOIN beatmapset bs ON bs.id = b.beatmapset_id
+            LEFT JOIN LATERAL (
+                SELECT MAX(sr.rating_value) AS value
+                FROM score_rating sr
+                JOIN rating_type rt ON rt.id = sr.rating_type_id
+                WHERE sr.score_id = s.id AND rt.name = 'pp'
+            ) pp ON true
+            WHERE pin.user_id = $1 AND b.mode = $2 AND NOT s.hidden
+            AND ($3::integer IS NULL OR s.mods = $3)
+            ORDER BY pin.pinned_at DESC, pin.score_id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            user_id,
+            mode,
+            mods,
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Épingle un score sur le profil de son joueur, dans la limite de [`MAX_PINNED_SCORES`]
+    pub async fn pin(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, score_id: i32) -> Result<PinResult, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        // Verrou sur l'utilisateur : deux épinglages simultanés ne peuvent pas dépasser la limite
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        // Les scores masqués ne sont plus affichés : leurs éventuels épinglages ne comptent pas
+        let pinned = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM user_pinned_score pin
+            JOIN score s ON s.id = pin.score_id
+            WHERE pin.user_id = $1 AND NOT s.hidden
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let already_pinned = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM user_pinned_score WHERE score_id = $1) AS "exists!""#,
+            score_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let result = if already_pinned {
+            PinResult::AlreadyPinned
+        } else if pinned >= MAX_PINNED_SCORES {
+            PinResult::LimitReached
+        } else {
+            sqlx::query!(
+                "INSERT INTO user_pinned_score (score_id, user_id) VALUES ($1, $2)",
+                score_id,
+                user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            PinResult::Pinned
+        };
+
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    /// Retire un score épinglé, retourne `false` s'il n'était pas épinglé
+    pub async fn unpin(pool: &sqlx::Pool<sqlx::Postgres>, score_id: i32) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM user_pinned_score WHERE score_id = $1", score_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::auth::{generate_token, hash_token};
+use crate::models::user::user::User;
+
+/// Durée de validité d'un lien de vérification
+pub const EMAIL_VERIFICATION_TTL_HOURS: i32 = 24;
+/// Délai minimum entre deux envois d'un lien de vérification
+pub const EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS: i64 = 60;
+
+/// Jeton de vérification d'adresse email, à usage unique
+pub struct EmailVerificationToken;
+
+impl EmailVerificationToken {
+    /// Crée un jeton pour l'email actuel de l'utilisateur et retourne sa valeur en clair
+    ///
+    /// Retourne `None` si un jeton a été émis pour cet email il y a moins de
+    /// [`EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS`] : la vérification et l'émission se font sous
+    /// le verrou de l'utilisateur, deux demandes simultanées n'envoient donc qu'un lien. Un
+    /// changement d'email n'est pas concerné par ce délai. Les jetons encore inutilisés sont invalidés.
+    pub async fn issue(pool: &sqlx::Pool<sqlx::Postgres>, user: &User) -> Result<Option<String>, sqlx::Error> {
+        let token = generate_token();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user.id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let recent = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM email_verification_token
+                WHERE user_id = $1 AND email = $2 AND created_at > now() - make_interval(secs => $3)
+            ) AS "recent!"
+            "#,
+            user.id,
+            user.email,
+            EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS as f64
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if recent {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM email_verification_token WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO email_verification_token (user_id, token_hash, email, expires_at)
+            VALUES ($1, $2, $3, now() + make_interval(hours => $4))
+            "#,
+            user.id,
+            hash_token(&token),
+            user.email,
+            EMAIL_VERIFICATION_TTL_HOURS
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(token))
+    }
+
+    /// Consomme un jeton et marque l'adresse email comme vérifiée
+    ///
+    /// Retourne `None` si le jeton est inconnu, expiré, déjà utilisé, ou si l'utilisateur
+    /// a changé d'email depuis son envoi.
+    pub async fn verify(pool: &sqlx::Pool<sqlx::Postgres>, token: &str) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let consumed = sqlx::query!(
+            r#"
+            UPDATE email_verification_token SET used_at = now()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+            RETURNING user_id, email
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(consumed) = consumed else {
+            return Ok(None);
+        };
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users SET is_verified = true, updated_at = now()
+            WHERE id = $1 AND email = $2
+            RETURNING id, username, email, password_hash, country, avatar_url, cover_url,
+                      is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
+            "#,
+            consumed.user_id,
+            consumed.email
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if user.is_some() {
+            tx.commit().await?;
+        }
+
+        Ok(user)
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::NaiveDateTime;
+use crate::models::user::user::SimplfiedUser;
+
+/// Relation "suit" entre deux utilisateurs, utilisée pour les classements entre amis
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserFollow {
+    pub follower_id: i32,
+    pub followed_id: i32,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl UserFollow {
+    /// Suit un utilisateur ; sans effet si c'est déjà le cas
+    pub async fn follow(pool: &sqlx::Pool<sqlx::Postgres>, follower_id: i32, followed_id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_follow (follower_id, followed_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            follower_id,
+            followed_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Arrête de suivre un utilisateur ; retourne `false` s'il n'était pas suivi
+    pub async fn unfollow(pool: &sqlx::Pool<sqlx::Postgres>, follower_id: i32, followed_id: i32) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_follow WHERE follower_id = $1 AND followed_id = $2
+            "#,
+            follower_id,
+            followed_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Utilisateurs suivis par un utilisateur
+    pub async fn get_following(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32) -> Result<Vec<SimplfiedUser>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            SimplfiedUser,
+            r#"
+            SELECT u.id, u.username, u.country
+            FROM user_follow f
+            JOIN users u ON u.id = f.followed_id
+            WHERE f.follower_id = $1
+            ORDER BY u.username
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Utilisateurs qui suivent un utilisateur
+    pub async fn get_followers(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32) -> Result<Vec<SimplfiedUser>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            SimplfiedUser,
+            r#"
+            SELECT u.id, u.username, u.country
+            FROM user_follow f
+            JOIN users u ON u.id = f.follower_id
+            WHERE f.followed_id = $1
+            ORDER BY u.username
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+}
@@ -1 +1,7 @@
-pub mod user; 
\ No newline at end of file
+pub mod user; 
+pub mod rank_history;
+pub mod follow;
+pub mod profile;
+pub mod email_verification;
+pub mod password_reset;
+pub mod session;
@@ -0,0 +1,126 @@
+use crate::auth::{generate_token, hash_token};
+
+/// Durée de validité d'un lien de réinitialisation
+pub const PASSWORD_RESET_TTL_MINUTES: i32 = 60;
+/// Délai minimum entre deux envois d'un lien de réinitialisation pour un même compte
+pub const PASSWORD_RESET_COOLDOWN_SECONDS: i64 = 60;
+
+/// Jeton de réinitialisation du mot de passe, à usage unique
+pub struct PasswordResetToken;
+
+impl PasswordResetToken {
+    /// Crée un jeton pour l'utilisateur et retourne sa valeur en clair
+    ///
+    /// Retourne `None` si un jeton a été émis il y a moins de [`PASSWORD_RESET_COOLDOWN_SECONDS`] :
+    /// la vérification et l'émission se font sous le verrou de l'utilisateur, deux demandes
+    /// simultanées n'envoient donc qu'un lien. Les jetons encore inutilisés sont invalidés.
+    pub async fn issue(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32) -> Result<Option<String>, sqlx::Error> {
+        let token = generate_token();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let recent = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM password_reset_token
+                WHERE user_id = $1 AND created_at > now() - make_interval(secs => $2)
+            ) AS "recent!"
+            "#,
+            user_id,
+            PASSWORD_RESET_COOLDOWN_SECONDS as f64
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if recent {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_reset_token WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_token (user_id, token_hash, expires_at)
+            VALUES ($1, $2, now() + make_interval(mins => $3))
+            "#,
+            user_id,
+            hash_token(&token),
+            PASSWORD_RESET_TTL_MINUTES
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(token))
+    }
+
+    /// Consomme un jeton et remplace le mot de passe (déjà haché)
+    ///
+    /// Les sessions et JWT déjà émis pour l'utilisateur sont révoqués. Retourne l'id de l'utilisateur,
+    /// ou `None` si le jeton est inconnu, expiré ou déjà utilisé.
+    pub async fn reset(pool: &sqlx::Pool<sqlx::Postgres>, token: &str, password_hash: &str) -> Result<Option<i32>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE password_reset_token SET used_at = now()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+            RETURNING user_id
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        // Les JWT ne portent que des secondes entières : la révocation est tronquée à la seconde
+        sqlx::query!(
+            r#"
+            UPDATE users SET
+                password_hash = $2,
+                sessions_revoked_at = date_trunc('second', now()),
+                updated_at = now()
+            WHERE id = $1
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_reset_token WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_session SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(user_id))
+    }
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::NaiveDateTime;
+use utoipa::ToSchema;
+use crate::models::user::user::User;
+use crate::models::score::score_rating::{ScoreRating, UserSkillProfile};
+use crate::models::score::user_score::{UserScore, UserScoreSchema};
+use crate::models::score::user_beatmap_best::ALL_MODS;
+
+/// Nombre de scores de chaque liste du profil (meilleurs, récents, premières places)
+pub const PROFILE_SCORES_LIMIT: i64 = 10;
+
+/// Nombre de meilleurs scores (un par beatmap) par rang
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GradeCounts {
+    /// SS avec Hidden ou Flashlight (XH)
+    pub ssh: i64,
+    /// SS (X ou SS)
+    pub ss: i64,
+    pub sh: i64,
+    pub s: i64,
+    pub a: i64,
+}
+
+/// Statistiques d'un utilisateur pour un mode
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserProfileStatistics {
+    /// pp total pondéré, absent si aucun score n'a de pp
+    pub pp: Option<f64>,
+    pub accuracy: Option<f64>,
+    /// Rang au dernier snapshot quotidien de `user_rank_history`
+    pub global_rank: Option<i32>,
+    /// Rang dans le pays au dernier snapshot quotidien
+    pub country_rank: Option<i32>,
+    /// Nombre de scores soumis (hors scores masqués)
+    pub play_count: i64,
+    /// Somme de tous les scores soumis
+    pub total_score: i64,
+    /// Somme des meilleurs scores, un par beatmap
+    pub ranked_score: i64,
+    pub max_combo: i32,
+    pub grade_counts: GradeCounts,
+    pub first_places_count: i64,
+}
+
+/// Bilan des matchs classés terminés d'un utilisateur pour un mode
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserRankedRecord {
+    pub matches_played: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub draws: i64,
+}
+
+/// Profil public d'un utilisateur pour un mode
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserProfile {
+    pub id: i32,
+    pub username: String,
+    pub country: String,
+    pub avatar_url: Option<String>,
+    pub cover_url: Option<String>,
+    pub is_verified: bool,
+    pub roles: Vec<String>,
+    pub last_visit: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>,
+    pub mode: i32,
+    pub statistics: UserProfileStatistics,
+    pub skills: UserSkillProfile,
+    pub ranked: UserRankedRecord,
+    /// Meilleurs scores par pp
+    pub top_plays: Vec<UserScoreSchema>,
+    pub recent_plays: Vec<UserScoreSchema>,
+    /// Premières places actuelles, les plus récentes d'abord
+    pub first_places: Vec<UserScoreSchema>,
+}
+
+impl UserProfile {
+    /// Construit le profil d'un utilisateur pour un mode
+    pub async fn build(pool: &sqlx::Pool<sqlx::Postgres>, user: &User, mode: i32) -> Result<Self, sqlx::Error> {
+        let statistics = Self::get_statistics(pool, user.id, mode).await?;
+        let ranked = Self::get_ranked_record(pool, user.id, mode).await?;
+        let skills = ScoreRating::get_user_skill_profile(pool, user.id, mode).await?;
+        let top_plays = UserScore::get_best(pool, user.id, mode, None, PROFILE_SCORES_LIMIT, 0).await?;
+        let recent_plays = UserScore::get_recent(pool, user.id, mode, None, PROFILE_SCORES_LIMIT, 0).await?;
+        let first_places = UserScore::get_firsts(pool, user.id, mode, None, PROFILE_SCORES_LIMIT, 0).await?;
+
+        Ok(Self {
+            id: user.id,
+            username: user.username.clone(),
+            country: user.country.clone(),
+            avatar_url: user.avatar_url.clone(),
+            cover_url: user.cover_url.clone(),
+            is_verified: user.is_verified,
+            roles: user.get_roles(),
+            last_visit: user.last_visit,
+            created_at: user.created_at,
+            mode,
+            statistics,
+            skills,
+            ranked,
+            top_plays: top_plays.iter().map(|s| s.to_schema()).collect(),
+            recent_plays: recent_plays.iter().map(|s| s.to_schema()).collect(),
+            first_places: first_places.iter().map(|s| s.to_schema()).collect(),
+        })
+    }
+
+    async fn get_statistics(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, mode: i32) -> Result<UserProfileStatistics, sqlx::Error> {
+        // pp et précision calculés en direct pour ce seul joueur, avec la même pondération
+        // que la vue `user_mode_stats` (dont le classement parcourt tous les joueurs)
+        let totals = sqlx::query!(
+            r#"
+            WITH best_per_beatmap AS (
+                SELECT DISTINCT ON (s.beatmap_id) s.accuracy, sr.rating_value AS pp
+                FROM score s
+                JOIN beatmap b ON b.id = s.beatmap_id
+                JOIN score_rating sr ON sr.score_id = s.id
+                JOIN rating_type rt ON rt.id = sr.rating_type_id AND rt.name = 'pp'
+                WHERE s.user_id = $1 AND b.mode = $2 AND NOT s.hidden
+                ORDER BY s.beatmap_id, sr.rating_value DESC
+            ), weighted AS (
+                SELECT accuracy, pp, power(0.95, row_number() OVER (ORDER BY pp DESC) - 1) AS weight
+                FROM best_per_beatmap
+            )
+            SELECT
+                SUM(pp * weight)::decimal(10,3) AS pp,
+                (SUM(accuracy * weight) / SUM(weight))::decimal(6,3) AS accuracy
+            FROM weighted
+            "#,
+            user_id,
+            mode
+        )
+        .fetch_one(pool)
+        .await?;
+
+        // Les rangs proviennent du dernier snapshot quotidien
+        let ranking = sqlx::query!(
+            r#"
+            SELECT global_rank, country_rank
+            FROM user_rank_history
+            WHERE user_id = $1 AND mode = $2
+            ORDER BY snapshot_date DESC
+            LIMIT 1
+            "#,
+            user_id,
+            mode
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let record = sqlx::query!(
+            r#"
+            WITH plays AS (
+                SELECT s.score, s.max_combo
+                FROM score s
+                JOIN beatmap b ON b.id = s.beatmap_id
+                WHERE s.user_id = $1 AND b.mode = $2 AND NOT s.hidden
+            ), best AS (
+                SELECT s.score, s.rank
+                FROM user_beatmap_best best
+                JOIN score s ON s.id = best.score_id
+                WHERE best.user_id = $1 AND best.mode = $2 AND best.mods = $3 AND best.criteria = 'score'
+            )
+            SELECT
+                (SELECT COUNT(*) FROM plays) AS "play_count!",
+                (SELECT COALESCE(SUM(score), 0)::bigint FROM plays) AS "total_score!",
+                (SELECT COALESCE(MAX(max_combo), 0) FROM plays) AS "max_combo!",
+                (SELECT COALESCE(SUM(score), 0)::bigint FROM best) AS "ranked_score!",
+                (SELECT COUNT(*) FROM best WHERE rank = 'XH') AS "count_ssh!",
+                (SELECT COUNT(*) FROM best WHERE rank IN ('X', 'SS')) AS "count_ss!",
+                (SELECT COUNT(*) FROM best WHERE rank = 'SH') AS "count_sh!",
+                (SELECT COUNT(*) FROM best WHERE rank = 'S') AS "count_s!",
+                (SELECT COUNT(*) FROM best WHERE rank = 'A') AS "count_a!",
+                (SELECT COUNT(*) FROM beatmap_first_place fp
+                 JOIN beatmap b ON b.id = fp.beatmap_id
+                 WHERE fp.user_id = $1 AND b.mode = $2) AS "first_places_count!"
+            "#,
+            user_id,
+            mode,
+            ALL_MODS
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(UserProfileStatistics {
+            pp: totals.pp
+                .map(|pp| pp.to_string().parse::<f64>().unwrap_or(0.0)),
+            accuracy: totals.accuracy
+                .map(|acc| acc.to_string().parse::<f64>().unwrap_or(0.0)),
+            global_rank: ranking.as_ref().map(|r| r.global_rank),
+            country_rank: ranking.as_ref().map(|r| r.country_rank),
+            play_count: record.play_count,
+            total_score: record.total_score,
+            ranked_score: record.ranked_score,
+            max_combo: record.max_combo,
+            grade_counts: GradeCounts {
+                ssh: record.count_ssh,
+                ss: record.count_ss,
+                sh: record.count_sh,
+                s: record.count_s,
+                a: record.count_a,
+            },
+            first_places_count: record.first_places_count,
+        })
+    }
+
+    async fn get_ranked_record(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, mode: i32) -> Result<UserRankedRecord, sqlx::Error> {
+        let record = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) AS "matches_played!",
+                COUNT(*) FILTER (
+                    WHERE (player1_id = $1 AND player1_points > player2_points)
+                    OR (player2_id = $1 AND player2_points > player1_points)
+                ) AS "wins!",
+                COUNT(*) FILTER (
+                    WHERE (player1_id = $1 AND player1_points < player2_points)
+                    OR (player2_id = $1 AND player2_points < player1_points)
+                ) AS "losses!"
+            FROM ranked_match
+            WHERE status = 'completed' AND mode = $2
+            AND (player1_id = $1 OR player2_id = $1)
+            "#,
+            user_id,
+            mode
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(UserRankedRecord {
+            matches_played: record.matches_played,
+            wins: record.wins,
+            losses: record.losses,
+            draws: record.matches_played - record.wins - record.losses,
+        })
+    }
+}
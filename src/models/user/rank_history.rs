@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::{chrono::NaiveDate, BigDecimal};
+use sqlx::PgPool;
+use chrono::{Duration as ChronoDuration, Utc};
+use std::time::Duration;
+use utoipa::ToSchema;
+use tracing::{error, info};
+use crate::config::HistoryConfig;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserRankHistory {
+    pub snapshot_date: NaiveDate,
+    pub global_rank: i32,
+    pub country_rank: i32,
+    pub pp: BigDecimal,
+    pub accuracy: BigDecimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserRankHistorySchema {
+    pub snapshot_date: NaiveDate,
+    pub global_rank: i32,
+    pub country_rank: i32,
+    pub pp: f64,
+    pub accuracy: f64,
+}
+
+impl UserRankHistory {
+    pub fn to_schema(&self) -> UserRankHistorySchema {
+        UserRankHistorySchema {
+            snapshot_date: self.snapshot_date,
+            global_rank: self.global_rank,
+            country_rank: self.country_rank,
+            pp: self.pp.to_string().parse::<f64>().unwrap_or(0.0),
+            accuracy: self.accuracy.to_string().parse::<f64>().unwrap_or(0.0),
+        }
+    }
+
+    /// Lance la tâche de fond qui prend un snapshot quotidien des rangs
+    ///
+    /// Un snapshot est pris au démarrage (il remplace celui du jour s'il existe),
+    /// puis chaque jour à minuit UTC, suivi de l'élagage des anciens snapshots.
+    pub async fn init(pool: PgPool, config: HistoryConfig) -> anyhow::Result<()> {
+        tokio::spawn(async move {
+            loop {
+                let today = Utc::now().date_naive();
+
+                match Self::snapshot(&pool, today).await {
+                    Ok(count) => info!("Rank history: {} snapshots pris pour le {}", count, today),
+                    Err(e) => error!("Rank history: échec du snapshot du {}: {}", today, e),
+                }
+
+                match Self::prune(&pool, &config).await {
+                    Ok(count) => info!("Rank history: {} anciens snapshots supprimés", count),
+                    Err(e) => error!("Rank history: échec de l'élagage: {}", e),
+                }
+
+                // Attendre jusqu'au prochain minuit UTC
+                let next_run = (today + ChronoDuration::days(1))
+                    .and_hms_opt(0, 0, 0)
+                    .expect("Minuit est toujours une heure valide")
+                    .and_utc();
+                let wait = (next_run - Utc::now()).to_std().unwrap_or(Duration::from_secs(60));
+                tokio::time::sleep(wait).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Enregistre les rangs, le pp et la précision de chaque utilisateur et mode pour une date
+    pub async fn snapshot(pool: &sqlx::Pool<sqlx::Postgres>, date: NaiveDate) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_rank_history (user_id, mode, global_rank, country_rank, pp, accuracy, snapshot_date)
+            SELECT user_id, mode, global_rank, country_rank, pp, accuracy, $1
+            FROM user_mode_stats
+            WHERE user_id IS NOT NULL AND mode IS NOT NULL
+            ON CONFLICT (user_id, mode, snapshot_date) DO UPDATE
+            SET global_rank = EXCLUDED.global_rank,
+                country_rank = EXCLUDED.country_rank,
+                pp = EXCLUDED.pp,
+                accuracy = EXCLUDED.accuracy
+            "#,
+            date
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Réduit l'historique ancien
+    ///
+    /// Au-delà de `retention_days`, seul le premier snapshot de chaque semaine est conservé.
+    /// Au-delà de `max_age_days`, les snapshots sont supprimés.
+    pub async fn prune(pool: &sqlx::Pool<sqlx::Postgres>, config: &HistoryConfig) -> Result<u64, sqlx::Error> {
+        let today = Utc::now().date_naive();
+        let downsample_before = today - ChronoDuration::days(config.retention_days);
+
+        let mut deleted = sqlx::query!(
+            r#"
+            DELETE FROM user_rank_history h
+            WHERE h.snapshot_date < $1
+            AND EXISTS (
+                SELECT 1 FROM user_rank_history o
+                WHERE o.user_id = h.user_id
+                AND o.mode = h.mode
+                AND date_trunc('week', o.snapshot_date) = date_trunc('week', h.snapshot_date)
+                AND o.snapshot_date < h.snapshot_date
+            )
+            "#,
+            downsample_before
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        if let Some(max_age_days) = config.max_age_days {
+            let delete_before = today - ChronoDuration::days(max_age_days);
+            deleted += sqlx::query!(
+                r#"
+                DELETE FROM user_rank_history WHERE snapshot_date < $1
+                "#,
+                delete_before
+            )
+            .execute(pool)
+            .await?
+            .rows_affected();
+        }
+
+        Ok(deleted)
+    }
+
+    /// Récupère la série temporelle d'un utilisateur pour un mode
+    pub async fn get_by_user(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, mode: i32) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            Self,
+            r#"
+            SELECT snapshot_date, global_rank, country_rank, pp, accuracy
+            FROM user_rank_history
+            WHERE user_id = $1 AND mode = $2
+            ORDER BY snapshot_date ASC
+            "#,
+            user_id,
+            mode
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+}
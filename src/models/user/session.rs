@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::NaiveDateTime;
+use utoipa::ToSchema;
+use crate::auth::{generate_token, hash_token};
+use crate::models::user::user::User;
+
+/// Session de l'utilisateur authentifié, insérée dans la requête par `auth_middleware`
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentSession(pub i32);
+
+/// Appareil et adresse à l'origine d'une connexion ou d'un renouvellement
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+/// Session de connexion, telle que listée à son propriétaire
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserSession {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// Dernier renouvellement du JWT d'accès
+    pub last_used_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    /// Session de la requête en cours
+    pub current: bool,
+}
+
+impl UserSession {
+    /// Ouvre une session et retourne son id et son refresh token en clair
+    ///
+    /// Les sessions expirées ou révoquées de l'utilisateur sont supprimées au passage.
+    pub async fn create(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        user_id: i32,
+        client: &SessionClient,
+        expiry_days: i32,
+    ) -> Result<(i32, String), sqlx::Error> {
+        let refresh_token = generate_token();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_session
+            WHERE user_id = $1 AND (expires_at <= now() OR revoked_at IS NOT NULL)
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO user_session (user_id, refresh_token_hash, user_agent, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4, now() + make_interval(days => $5))
+            RETURNING id
+            "#,
+            user_id,
+            hash_token(&refresh_token),
+            client.user_agent,
+            client.ip_address,
+            expiry_days
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok((id, refresh_token))
+    }
+
+    /// Échange un refresh token contre un nouveau et prolonge la session
+    ///
+    /// Retourne l'utilisateur, l'id de session et le nouveau refresh token, ou `None` si le
+    /// token est inconnu, expiré ou révoqué. Les tokens renouvelés sont conservés : présenter
+    /// n'importe lequel d'entre eux révoque la session, il a probablement été volé.
+    pub async fn refresh(
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        refresh_token: &str,
+        client: &SessionClient,
+        expiry_days: i32,
+    ) -> Result<Option<(User, i32, String)>, sqlx::Error> {
+        let token_hash = hash_token(refresh_token);
+        let new_token = generate_token();
+        let mut tx = pool.begin().await?;
+
+        let session = sqlx::query!(
+            r#"
+            UPDATE user_session SET
+                refresh_token_hash = $2,
+                user_agent = COALESCE($3, user_agent),
+                ip_address = COALESCE($4, ip_address),
+                last_used_at = now(),
+                expires_at = now() + make_interval(days => $5)
+            WHERE refresh_token_hash = $1 AND revoked_at IS NULL AND expires_at > now()
+            RETURNING id, user_id
+            "#,
+            token_hash,
+            hash_token(&new_token),
+            client.user_agent,
+            client.ip_address,
+            expiry_days
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(session) = session else {
+            sqlx::query!(
+                r#"
+                UPDATE user_session SET revoked_at = now()
+                WHERE id = (SELECT session_id FROM user_session_rotated_token WHERE token_hash = $1)
+                AND revoked_at IS NULL
+                "#,
+                token_hash
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_session_rotated_token (token_hash, session_id) VALUES ($1, $2)
+            "#,
+            token_hash,
+            session.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let user = User::get_by_id(pool, session.user_id).await?;
+        Ok(user.map(|user| (user, session.id, new_token)))
+    }
+
+    /// Utilisateur d'une session encore active, pour l'authentification des requêtes
+    pub async fn get_active_user(pool: &sqlx::Pool<sqlx::Postgres>, session_id: i32, user_id: i32) -> Result<Option<User>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.id, u.username, u.email, u.password_hash, u.country, u.avatar_url, u.cover_url,
+                   u.is_verified, u.last_visit, u.created_at, u.updated_at, u.roles as "roles: sqlx::types::JsonValue", u.sessions_revoked_at
+            FROM user_session s
+            JOIN users u ON u.id = s.user_id
+            WHERE s.id = $1 AND s.user_id = $2 AND s.revoked_at IS NULL AND s.expires_at > now()
+            "#,
+            session_id,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Sessions actives de l'utilisateur, les plus récemment utilisées d'abord
+    pub async fn get_by_user(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, current_session_id: i32) -> Result<Vec<Self>, sqlx::Error> {
+        let records = sqlx::query_as!(
+            UserSession,
+            r#"
+            SELECT id, user_agent, ip_address, created_at, last_used_at, expires_at, id = $2 AS "current!"
+            FROM user_session
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > now()
+            ORDER BY last_used_at DESC
+            "#,
+            user_id,
+            current_session_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Révoque une session de l'utilisateur ; retourne `false` si elle n'existe pas ou est déjà révoquée
+    pub async fn revoke(pool: &sqlx::Pool<sqlx::Postgres>, user_id: i32, session_id: i32) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_session SET revoked_at = now()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            session_id,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
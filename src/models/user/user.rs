@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
 use sqlx::types::chrono::NaiveDateTime;
+use sqlx::PgConnection;
 use fake::Dummy;
 use validator::Validate;
 use fake::faker::internet::en::FreeEmail;
 use fake::faker::address::en::CountryCode;
 use utoipa::ToSchema;
+use crate::helpers::image::ProfileImageKind;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct User {
@@ -21,9 +23,12 @@ pub struct User {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub roles: sqlx::types::JsonValue,
+    /// Les JWT émis avant cette date sont refusés
+    #[serde(skip_serializing)]
+    pub sessions_revoked_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct SimplfiedUser{
     pub id: i32,
     pub username: String,
@@ -46,6 +51,40 @@ pub struct CreateUser {
     pub country: String,
 }
 
+/// Délai minimum entre deux changements de pseudo
+pub const USERNAME_CHANGE_COOLDOWN_DAYS: i32 = 30;
+/// Durée pendant laquelle un ancien pseudo reste réservé et mène à son utilisateur
+pub const USERNAME_GRACE_PERIOD_DAYS: i32 = 90;
+
+/// Modifications de compte demandées par un utilisateur, déjà validées
+#[derive(Debug, Default)]
+pub struct AccountChanges {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    /// Nouveau mot de passe, déjà haché
+    pub password_hash: Option<String>,
+    pub country: Option<String>,
+}
+
+/// Résultat d'une modification de compte
+#[derive(Debug)]
+pub enum AccountUpdate {
+    Updated(Box<User>),
+    /// Pseudo utilisé par un autre utilisateur, ou ancien pseudo encore réservé
+    UsernameTaken,
+    EmailTaken,
+    /// Pseudo changé trop récemment : nouveau changement possible à partir de cette date
+    UsernameCooldown(NaiveDateTime),
+}
+
+/// Résultat d'une inscription
+#[derive(Debug)]
+pub enum UserCreation {
+    Created(Box<User>),
+    /// Pseudo et/ou email déjà utilisés ; aucun compte n'a été créé
+    Taken { username: bool, email: bool },
+}
+
 impl User {
     pub fn get_roles(&self) -> Vec<String> {
         self.roles
@@ -65,7 +104,7 @@ impl User {
             User,
             r#"
             SELECT id, username, email, password_hash, country, avatar_url, cover_url, 
-                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue"
+                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
             FROM users WHERE id = $1
             "#,
             id
@@ -81,7 +120,7 @@ impl User {
             User,
             r#"
             SELECT id, username, email, password_hash, country, avatar_url, cover_url, 
-                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue"
+                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
             FROM users WHERE username = $1
             "#,
             username
@@ -92,13 +131,37 @@ impl User {
         Ok(record)
     }
 
+    pub async fn get_by_email(pool: &sqlx::Pool<sqlx::Postgres>, email: &str) -> Result<Option<Self>, sqlx::Error> {
+        let record = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, username, email, password_hash, country, avatar_url, cover_url, 
+                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
+            FROM users WHERE lower(email) = lower($1)
+            "#,
+            email
+        )
+        .fetch_optional(pool)
+        .await?;
+    
+        Ok(record)
+    }
+
+    /// Indique si un JWT émis à `issued_at` (timestamp unix) est encore accepté
+    pub fn accepts_token_issued_at(&self, issued_at: usize) -> bool {
+        match self.sessions_revoked_at {
+            Some(revoked_at) => issued_at as i64 >= revoked_at.and_utc().timestamp(),
+            None => true,
+        }
+    }
+
     pub async fn get_all(pool: &sqlx::Pool<sqlx::Postgres>, page: i64, per_page: i64) -> Result<Vec<Self>, sqlx::Error> {
         let offset = (page - 1) * per_page;
         let records = sqlx::query_as!(
             User,
             r#"
             SELECT id, username, email, password_hash, country, avatar_url, cover_url, 
-                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue"
+                   is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
             FROM users
             ORDER BY id
             LIMIT $1 OFFSET $2
@@ -112,7 +175,292 @@ impl User {
         Ok(records)
     }
 
-    pub async fn create(pool: &sqlx::Pool<sqlx::Postgres>, user: CreateUser) -> Result<Self, sqlx::Error> {
+    /// Verrouille un fichier d'image de profil jusqu'à la fin de la transaction
+    ///
+    /// Les fichiers étant nommés d'après leur contenu, une même image peut être partagée :
+    /// son écriture et son association, comme sa suppression, se font sous ce verrou pour
+    /// qu'un fichier ne soit jamais supprimé alors qu'un envoi concurrent va le référencer.
+    pub async fn lock_profile_image(conn: &mut PgConnection, url: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))", url)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remplace l'avatar ou la bannière d'un utilisateur
+    ///
+    /// Retourne l'utilisateur mis à jour et l'URL de l'image précédente, ou `None` si l'utilisateur n'existe pas.
+    pub async fn set_profile_image(
+        conn: &mut PgConnection,
+        id: i32,
+        kind: ProfileImageKind,
+        url: &str
+    ) -> Result<Option<(Self, Option<String>)>, sqlx::Error> {
+        let previous = match kind {
+            ProfileImageKind::Avatar => sqlx::query_scalar!("SELECT avatar_url FROM users WHERE id = $1 FOR UPDATE", id)
+                .fetch_optional(&mut *conn)
+                .await?,
+            ProfileImageKind::Cover => sqlx::query_scalar!("SELECT cover_url FROM users WHERE id = $1 FOR UPDATE", id)
+                .fetch_optional(&mut *conn)
+                .await?,
+        };
+        let Some(previous) = previous else {
+            return Ok(None);
+        };
+
+        let record = match kind {
+            ProfileImageKind::Avatar => sqlx::query_as!(
+                User,
+                r#"
+                UPDATE users SET avatar_url = $2, updated_at = now()
+                WHERE id = $1
+                RETURNING id, username, email, password_hash, country, avatar_url, cover_url, 
+                          is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
+                "#,
+                id,
+                url
+            )
+            .fetch_one(&mut *conn)
+            .await?,
+            ProfileImageKind::Cover => sqlx::query_as!(
+                User,
+                r#"
+                UPDATE users SET cover_url = $2, updated_at = now()
+                WHERE id = $1
+                RETURNING id, username, email, password_hash, country, avatar_url, cover_url, 
+                          is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
+                "#,
+                id,
+                url
+            )
+            .fetch_one(&mut *conn)
+            .await?,
+        };
+
+        Ok(Some((record, previous)))
+    }
+
+    /// Indique si une image est encore utilisée comme avatar ou bannière par un utilisateur
+    ///
+    /// À appeler sous [`User::lock_profile_image`] avant de supprimer le fichier.
+    pub async fn is_profile_image_used(conn: &mut PgConnection, url: &str) -> Result<bool, sqlx::Error> {
+        let used = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE avatar_url = $1 OR cover_url = $1) AS "used!""#,
+            url
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(used)
+    }
+
+    /// Utilisateur portant ce pseudo, ou l'ayant porté pendant les [`USERNAME_GRACE_PERIOD_DAYS`] derniers jours
+    pub async fn get_by_current_or_previous_username(pool: &sqlx::Pool<sqlx::Postgres>, username: &str) -> Result<Option<Self>, sqlx::Error> {
+        if let Some(user) = Self::get_by_username(pool, username).await? {
+            return Ok(Some(user));
+        }
+
+        let record = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.id, u.username, u.email, u.password_hash, u.country, u.avatar_url, u.cover_url, 
+                   u.is_verified, u.last_visit, u.created_at, u.updated_at, u.roles as "roles: sqlx::types::JsonValue", u.sessions_revoked_at
+            FROM username_history h
+            JOIN users u ON u.id = h.user_id
+            WHERE h.old_username = $1 AND h.changed_at > now() - make_interval(days => $2)
+            ORDER BY h.changed_at DESC
+            LIMIT 1
+            "#,
+            username,
+            USERNAME_GRACE_PERIOD_DAYS
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Applique des modifications de compte
+    ///
+    /// Un changement de pseudo est soumis au délai [`USERNAME_CHANGE_COOLDOWN_DAYS`] et enregistré
+    /// dans `username_history`. Un changement d'email repasse le compte en non vérifié.
+    /// Un changement de mot de passe révoque toutes les sessions du joueur sauf `session_id`.
+    pub async fn update_account(pool: &sqlx::Pool<sqlx::Postgres>, id: i32, session_id: i32, changes: AccountChanges) -> Result<AccountUpdate, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let current = sqlx::query!("SELECT username, email FROM users WHERE id = $1 FOR UPDATE", id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let username = changes.username.filter(|username| *username != current.username);
+        if let Some(username) = &username {
+            let taken = sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(SELECT 1 FROM users WHERE lower(username) = lower($1) AND id <> $2)
+                    OR EXISTS(
+                        SELECT 1 FROM username_history
+                        WHERE lower(old_username) = lower($1) AND user_id <> $2
+                        AND changed_at > now() - make_interval(days => $3)
+                    ) AS "taken!"
+                "#,
+                username,
+                id,
+                USERNAME_GRACE_PERIOD_DAYS
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if taken {
+                return Ok(AccountUpdate::UsernameTaken);
+            }
+        }
+
+        let email = changes.email.filter(|email| *email != current.email);
+        if let Some(email) = &email {
+            let taken = sqlx::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM users WHERE lower(email) = lower($1) AND id <> $2) AS "taken!""#,
+                email,
+                id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if taken {
+                return Ok(AccountUpdate::EmailTaken);
+            }
+        }
+
+        // Le délai entre deux changements de pseudo est vérifié par l'UPDATE lui-même, avec l'horloge
+        // de la base : aucune ligne modifiée signifie que le délai n'est pas écoulé
+        let record = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users SET
+                username = COALESCE($2, username),
+                email = COALESCE($3, email),
+                password_hash = COALESCE($4, password_hash),
+                country = COALESCE($5, country),
+                is_verified = is_verified AND $3::varchar IS NULL,
+                updated_at = now()
+            WHERE id = $1
+            AND ($2::varchar IS NULL OR NOT EXISTS(
+                SELECT 1 FROM username_history
+                WHERE user_id = $1 AND changed_at > now() - make_interval(days => $6)
+            ))
+            RETURNING id, username, email, password_hash, country, avatar_url, cover_url, 
+                      is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
+            "#,
+            id,
+            username,
+            email,
+            changes.password_hash,
+            changes.country,
+            USERNAME_CHANGE_COOLDOWN_DAYS
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(record) = record else {
+            let next_change = sqlx::query_scalar!(
+                r#"
+                SELECT MAX(changed_at) + make_interval(days => $2) AS "next_change!"
+                FROM username_history
+                WHERE user_id = $1
+                "#,
+                id,
+                USERNAME_CHANGE_COOLDOWN_DAYS
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            return Ok(AccountUpdate::UsernameCooldown(next_change));
+        };
+
+        if let Some(username) = &username {
+            sqlx::query!(
+                "INSERT INTO username_history (user_id, old_username, new_username) VALUES ($1, $2, $3)",
+                id,
+                current.username,
+                username
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Un changement de mot de passe déconnecte les autres appareils, comme une réinitialisation
+        if changes.password_hash.is_some() {
+            sqlx::query!(
+                r#"
+                UPDATE user_session SET revoked_at = now()
+                WHERE user_id = $1 AND id <> $2 AND revoked_at IS NULL
+                "#,
+                id,
+                session_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(AccountUpdate::Updated(Box::new(record)))
+    }
+
+    /// Crée un compte ; refuse un pseudo (ou ancien pseudo encore réservé) ou un email déjà utilisé
+    ///
+    /// `user.password` doit déjà être haché.
+    pub async fn create(pool: &sqlx::Pool<sqlx::Postgres>, user: CreateUser) -> Result<UserCreation, sqlx::Error> {
+        let taken = sqlx::query!(
+            r#"
+            SELECT
+                EXISTS(SELECT 1 FROM users WHERE lower(username) = lower($1))
+                    OR EXISTS(
+                        SELECT 1 FROM username_history
+                        WHERE lower(old_username) = lower($1)
+                        AND changed_at > now() - make_interval(days => $3)
+                    ) AS "username!",
+                EXISTS(SELECT 1 FROM users WHERE lower(email) = lower($2)) AS "email!"
+            "#,
+            user.username,
+            user.email,
+            USERNAME_GRACE_PERIOD_DAYS
+        )
+        .fetch_one(pool)
+        .await?;
+        if taken.username || taken.email {
+            return Ok(UserCreation::Taken { username: taken.username, email: taken.email });
+        }
+
+        let record = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (username, email, password_hash, country) 
+            VALUES ($1, $2, $3, $4) 
+            RETURNING id, username, email, password_hash, country, avatar_url, cover_url, 
+                      is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
+            "#,
+            user.username,
+            user.email,
+            user.password,
+            user.country
+        )
+        .fetch_one(pool)
+        .await;
+
+        // Inscription concurrente avec le même pseudo ou email entre la vérification et l'insertion
+        match record {
+            Ok(record) => Ok(UserCreation::Created(Box::new(record))),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(UserCreation::Taken {
+                username: e.constraint() == Some("users_username_key"),
+                email: e.constraint() == Some("users_email_key"),
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Retourne l'utilisateur portant ce pseudo, ou le crée
+    ///
+    /// Réservé aux fixtures : ne vérifie ni le mot de passe ni l'email, et ne doit jamais
+    /// être exposé par une route.
+    pub(crate) async fn get_or_create(pool: &sqlx::Pool<sqlx::Postgres>, user: CreateUser) -> Result<Self, sqlx::Error> {
         let record = sqlx::query_as!(
             User,
             r#"
@@ -120,7 +468,7 @@ impl User {
             VALUES ($1, $2, $3, $4) 
             ON CONFLICT (username) DO NOTHING
             RETURNING id, username, email, password_hash, country, avatar_url, cover_url, 
-                      is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue"
+                      is_verified, last_visit, created_at, updated_at, roles as "roles: sqlx::types::JsonValue", sessions_revoked_at
             "#,
             user.username,
             user.email,
@@ -1,24 +1,35 @@
 use axum::{
     middleware,
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use crate::middleware::auth::auth_middleware;
 use sqlx::PgPool;
 use crate::db::DatabaseManager;
-use crate::handlers::auth::me;
+use crate::handlers::auth::{
+    me, verify_email, resend_email_verification, forgot_password, reset_password,
+    refresh_token, logout, get_sessions, revoke_session,
+};
 use crate::auth::{login, register};
 
 pub fn router(pool: PgPool) -> Router<DatabaseManager> {
     let public_routes = Router::new()
         .route("/auth/login", post(login))
-        .route("/auth/register", post(register));
+        .route("/auth/register", post(register))
+        .route("/auth/verify", post(verify_email))
+        .route("/auth/forgot", post(forgot_password))
+        .route("/auth/reset", post(reset_password))
+        .route("/auth/refresh", post(refresh_token));
 
     let protected_routes = Router::new()
         .route("/auth/me", get(me))
+        .route("/auth/verify/resend", post(resend_email_verification))
+        .route("/auth/logout", post(logout))
+        .route("/auth/sessions", get(get_sessions))
+        .route("/auth/sessions/{id}", delete(revoke_session))
         .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware));
 
     public_routes
         .merge(protected_routes)
         .with_state(pool)
-} 
\ No newline at end of file
+}
@@ -0,0 +1,17 @@
+//! # Event Routes Module
+//!
+//! Ce module configure les routes de consultation des événements, réservées aux administrateurs.
+
+use axum::{routing::get, Router, middleware};
+use crate::db::DatabaseManager;
+use sqlx::PgPool;
+use crate::handlers::event::get_events;
+use crate::middleware::auth::{auth_middleware, admin_middleware};
+
+pub fn router(pool: PgPool) -> Router<DatabaseManager> {
+    Router::new()
+        .route("/events", get(get_events))
+        .layer(middleware::from_fn(admin_middleware))
+        .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware))
+        .with_state(pool)
+}
@@ -1,11 +1,15 @@
 use axum::{routing::get, Router};
 use crate::db::DatabaseManager;
 use sqlx::PgPool;
-use crate::handlers::map::beatmap::{get_beatmap, get_random};
+use crate::handlers::map::beatmap::{get_beatmap, get_random, get_beatmap_skillsets, get_beatmap_attributes, simulate_beatmap_pp, get_beatmap_strains};
 
 pub fn router(pool: PgPool) -> Router<DatabaseManager> {
     Router::new()
         .route("/beatmapset/{beatmapset_id}/beatmap", get(get_beatmap))
         .route("/beatmap/random", get(get_random))
+        .route("/beatmap/{id}/skillsets", get(get_beatmap_skillsets))
+        .route("/beatmap/{id}/attributes", get(get_beatmap_attributes))
+        .route("/beatmap/{id}/pp", get(simulate_beatmap_pp))
+        .route("/beatmap/{id}/strains", get(get_beatmap_strains))
         .with_state(pool)
 }
\ No newline at end of file
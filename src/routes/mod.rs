@@ -26,28 +26,108 @@ pub mod score;
 pub mod public;
 pub mod auth;
 pub mod ranked;
+pub mod event;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::handlers::user::get_user_by_id,
+        crate::handlers::user::get_user_by_name,
         crate::handlers::user::get_users,
+        crate::handlers::user::get_user_history,
+        crate::handlers::user::get_user_overall_rating,
+        crate::handlers::user::get_user_skillsets,
+        crate::handlers::user::get_user_chokes,
+        crate::handlers::user::get_user_skill_profile,
+        crate::handlers::user::follow_user,
+        crate::handlers::user::unfollow_user,
+        crate::handlers::user::get_user_following,
+        crate::handlers::user::get_user_followers,
+        crate::handlers::user::get_user_first_places,
+        crate::handlers::user::get_user_sniped,
+        crate::handlers::user::get_user_scores,
+        crate::handlers::user::upload_avatar,
+        crate::handlers::user::upload_cover,
+        crate::handlers::user::update_me,
+        crate::handlers::score::score::get_recent_snipes,
+        crate::handlers::score::score::pin_score,
+        crate::handlers::score::score::unpin_score,
+        crate::handlers::event::get_events,
+        crate::handlers::auth::verify_email,
+        crate::handlers::auth::resend_email_verification,
+        crate::handlers::auth::forgot_password,
+        crate::handlers::auth::reset_password,
+        crate::handlers::auth::refresh_token,
+        crate::handlers::auth::logout,
+        crate::handlers::auth::get_sessions,
+        crate::handlers::auth::revoke_session,
         crate::handlers::map::beatmap::get_beatmap,
         crate::handlers::map::beatmap::get_random,
+        crate::handlers::map::beatmap::get_beatmap_skillsets,
+        crate::handlers::map::beatmap::get_beatmap_attributes,
+        crate::handlers::map::beatmap::simulate_beatmap_pp,
+        crate::handlers::map::beatmap::get_beatmap_strains,
         crate::handlers::map::beatmapset::get_beatmapsets,
         crate::handlers::map::beatmapset::get_beatmapset_by_id,
         crate::handlers::score::score::get_leaderboard,
-        crate::handlers::score::pp_calculator::calculate_missing_pp,
+        crate::handlers::score::score::get_my_leaderboard_standing,
+        crate::handlers::score::score::get_user_leaderboard_standing,
+        crate::handlers::score::score::get_score,
+        crate::handlers::score::score::update_score,
+        crate::handlers::score::score::delete_score,
+        crate::handlers::score::pp_calculator::create_rating_job,
+        crate::handlers::score::pp_calculator::get_rating_jobs,
+        crate::handlers::score::pp_calculator::get_rating_job,
+        crate::handlers::score::pp_calculator::cancel_rating_job,
+        crate::handlers::score::pp_calculator::get_rating_job_deltas,
     ),
     components(
         schemas(
             crate::models::user::user::User,
+            crate::models::user::profile::UserProfile,
+            crate::handlers::user::ProfileImageUpload,
+            crate::handlers::user::UpdateAccountRequest,
+            crate::models::user::profile::UserProfileStatistics,
+            crate::models::user::profile::GradeCounts,
+            crate::models::user::profile::UserRankedRecord,
+            crate::models::score::user_score::UserScoreSchema,
+            crate::models::score::user_score::ScoreBeatmap,
+            crate::models::user::rank_history::UserRankHistorySchema,
+            crate::models::score::score_rating::UserOverallRating,
+            crate::models::score::skillset::Skillsets,
+            crate::models::score::skillset::SkillsetSchema,
             crate::models::map::beatmap::BeatmapSchema,
+            crate::models::map::beatmap_difficulty::BeatmapDifficultySchema,
             crate::models::map::beatmapset::BeatmapsetSchema,
             crate::handlers::score::score::LeaderboardParams,
             crate::models::score::score::LeaderboardSchema,
-            crate::handlers::score::pp_calculator::PPCalculationParams,
-            crate::handlers::score::pp_calculator::PPCalculationResponse,
+            crate::models::score::score::LeaderboardPageSchema,
+            crate::models::score::score::LeaderboardStandingSchema,
+            crate::handlers::score::score::LeaderboardStandingParams,
+            crate::models::score::score::ScoreSchema,
+            crate::models::score::score::ScoreDetailSchema,
+            crate::handlers::score::score::UpdateScore,
+            crate::models::score::score_rating::ScoreRatingSchema,
+            crate::models::score::score_rating::ScoreChokeSchema,
+            crate::models::score::score_rating::UserSkillProfile,
+            crate::models::score::first_place::BeatmapFirstPlace,
+            crate::models::score::first_place::FirstPlaceChange,
+            crate::models::event::Event,
+            crate::handlers::event::EventParams,
+            crate::handlers::auth::VerifyEmailRequest,
+            crate::handlers::auth::ForgotPasswordRequest,
+            crate::handlers::auth::ResetPasswordRequest,
+            crate::handlers::auth::RefreshRequest,
+            crate::handlers::auth::RefreshResponse,
+            crate::models::user::session::UserSession,
+            crate::helpers::pp::PpSimulation,
+            crate::helpers::pp::PpBreakdown,
+            crate::helpers::difficulty::StrainGraph,
+            crate::helpers::difficulty::StrainSeries,
+            crate::handlers::score::pp_calculator::DeltaParams,
+            crate::models::score::rating_job::RatingRecalcJob,
+            crate::models::score::rating_job::CreateRatingRecalcJob,
+            crate::models::score::rating_job::RatingDeltaSchema,
             crate::models::map::beatmap::RandomBeatmapQuerySchema,
         )
     ),
@@ -56,6 +136,8 @@ pub mod ranked;
         (name = "Beatmap", description = "Beatmap management endpoints"),
         (name = "Beatmapsets", description = "Beatmapset management endpoints"),
         (name = "Score", description = "Score management endpoints"),
+        (name = "Event", description = "Event feed endpoints"),
+        (name = "Auth", description = "Authentication endpoints"),
     )
 )]
 pub struct ApiDoc;
@@ -71,6 +153,7 @@ pub fn create_router(db: DatabaseManager) -> Router {
         .nest("/api", score::score::router(db.get_pool().clone()))
         .nest("/api", auth::router(db.get_pool().clone()))
         .nest("/api", ranked::router(db.get_pool().clone()))
+        .nest("/api", event::router(db.get_pool().clone()))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .merge(public::router(db.get_pool().clone()))
         .with_state(db)
@@ -13,7 +13,7 @@ use crate::{
         set_ready,
     },
     db::DatabaseManager,
-    middleware::auth::auth_middleware,
+    middleware::auth::{auth_middleware, verified_middleware},
 };
 
 pub fn router(pool: PgPool) -> Router<DatabaseManager> {
@@ -23,6 +23,7 @@ pub fn router(pool: PgPool) -> Router<DatabaseManager> {
         .route("/ranked/queue/status", get(get_queue_status))
         .route("/ranked/match/status", get(get_match_status))
         .route("/ranked/match/ready", post(set_ready))
+        .layer(middleware::from_fn(verified_middleware))
         .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware))
         .with_state(pool)
     } 
\ No newline at end of file
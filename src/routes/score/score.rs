@@ -1,16 +1,40 @@
-use axum::{routing::{get, post}, Router, middleware};
+use axum::{routing::{get, patch, post}, Router, middleware};
 use crate::db::DatabaseManager;
 use sqlx::PgPool;
-use crate::handlers::score::score::get_leaderboard;
+use crate::handlers::score::score::{
+    get_leaderboard, get_my_leaderboard_standing, get_user_leaderboard_standing, get_score, update_score, delete_score,
+    get_recent_snipes, pin_score, unpin_score,
+};
 use crate::handlers::score::loadingscore::load_scores_db;
-use crate::handlers::score::pp_calculator::calculate_missing_pp;
-use crate::middleware::auth::auth_middleware;
+use crate::handlers::score::pp_calculator::{
+    create_rating_job, get_rating_jobs, get_rating_job, cancel_rating_job, get_rating_job_deltas,
+};
+use crate::middleware::auth::{auth_middleware, admin_middleware, verified_middleware};
 
 pub fn router(pool: PgPool) -> Router<DatabaseManager> {
+    let admin = Router::new()
+        .route("/scores/rating-jobs", get(get_rating_jobs).post(create_rating_job))
+        .route("/scores/rating-jobs/{id}", get(get_rating_job))
+        .route("/scores/rating-jobs/{id}/cancel", post(cancel_rating_job))
+        .route("/scores/rating-jobs/{id}/deltas", get(get_rating_job_deltas))
+        .layer(middleware::from_fn(admin_middleware))
+        .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware));
+
+    let verified = Router::new()
+        .route("/scores/load", post(load_scores_db))
+        .layer(middleware::from_fn(verified_middleware))
+        .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware));
+
     Router::new()
         .route("/leaderboard/{beatmap_id}", get(get_leaderboard))
-        .route("/scores/load", post(load_scores_db))
+        .route("/leaderboard/{beatmap_id}/me", get(get_my_leaderboard_standing))
+        .route("/leaderboard/{beatmap_id}/user/{user_id}", get(get_user_leaderboard_standing))
+        .route("/score/{id}", patch(update_score).delete(delete_score))
+        .route("/score/{id}/pin", post(pin_score).delete(unpin_score))
             .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware))
-        .route("/scores/calculate-pp", post(calculate_missing_pp))
+        .route("/score/{id}", get(get_score))
+        .route("/scores/snipes", get(get_recent_snipes))
+        .merge(verified)
+        .merge(admin)
         .with_state(pool)
 }
\ No newline at end of file
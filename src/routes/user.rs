@@ -1,11 +1,39 @@
-use axum::{routing::get, Router};
+use axum::{routing::{get, patch, post, put}, Router, middleware, extract::DefaultBodyLimit};
 use crate::db::DatabaseManager;
 use sqlx::PgPool;
-use crate::handlers::user::{get_user_by_id, get_users};
+use crate::handlers::user::{
+    get_user_by_id, get_user_by_name, get_users, get_user_history, get_user_overall_rating, get_user_skillsets, get_user_chokes,
+    get_user_skill_profile, follow_user, unfollow_user, get_user_following, get_user_followers,
+    get_user_first_places, get_user_sniped, get_user_scores, upload_avatar, upload_cover,
+    update_me,
+};
+use crate::middleware::auth::auth_middleware;
+use crate::helpers::image::ProfileImageKind;
 
 pub fn router(pool: PgPool) -> Router<DatabaseManager> {
+    let protected = Router::new()
+        .route("/user/{id}/follow", post(follow_user).delete(unfollow_user))
+        .route("/user/me", patch(update_me))
+        .route("/user/me/avatar", put(upload_avatar))
+        .route("/user/me/cover", put(upload_cover))
+        // Marge pour l'enveloppe multipart au-delà du poids maximum d'une image
+        .layer(DefaultBodyLimit::max(ProfileImageKind::Cover.max_bytes() + 64 * 1024))
+        .layer(middleware::from_fn_with_state(pool.clone(), auth_middleware));
+
     Router::new()
         .route("/user/{id}", get(get_user_by_id))
+        .route("/user/by-name/{username}", get(get_user_by_name))
+        .route("/user/{id}/history", get(get_user_history))
+        .route("/user/{id}/ratings/{rating_type}", get(get_user_overall_rating))
+        .route("/user/{id}/skillsets", get(get_user_skillsets))
+        .route("/user/{id}/chokes", get(get_user_chokes))
+        .route("/user/{id}/skills", get(get_user_skill_profile))
+        .route("/user/{id}/following", get(get_user_following))
+        .route("/user/{id}/followers", get(get_user_followers))
+        .route("/user/{id}/first-places", get(get_user_first_places))
+        .route("/user/{id}/sniped", get(get_user_sniped))
+        .route("/user/{id}/scores/{kind}", get(get_user_scores))
         .route("/user", get(get_users))
+        .merge(protected)
         .with_state(pool)
-}
\ No newline at end of file
+}